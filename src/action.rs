@@ -1,20 +1,15 @@
-use crossterm::event::KeyEvent;
-
-/// A new component to be rendered on the UI.
-/// Used in conjunction with [`Action`]
-#[derive(Clone, Copy)]
-pub enum Page {
-    Home,
-    Details,
-}
+use crossterm::event::{KeyEvent, MouseEvent};
 
 #[derive(Clone, Copy)]
 pub enum Action {
     Tick,
     Key(KeyEvent),
+    Mouse(MouseEvent),
+    /// The terminal was resized to this width and height.
+    Resize(u16, u16),
+    /// Stop the process until it is resumed by the shell, with `fg`.
+    Suspend,
     Quit,
     Render,
     None,
-    /// Render another page on the UI
-    ChangePage(Page),
 }
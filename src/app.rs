@@ -1,12 +1,24 @@
+use std::path::PathBuf;
+
 use color_eyre::eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Rect;
 use tokio::sync::mpsc::{
     unbounded_channel, UnboundedReceiver, UnboundedSender,
 };
 
 use crate::{
-    action::{self, Action},
-    pages::{home::Home, Page, details::Details},
-    tui::Tui,
+    action::Action,
+    csv,
+    document::{Document, SharedDocument},
+    pages::{
+        diff::{Diff, Side},
+        home::Home,
+        Page,
+    },
+    pipe,
+    schema::Schema,
+    tui::{Event, Tui},
 };
 
 pub struct App {
@@ -14,18 +26,57 @@ pub struct App {
     tx: UnboundedSender<Action>,
     rx: Option<UnboundedReceiver<Action>>,
     page: Box<dyn Page>,
+    /// The table read from stdin, when a file is `-`.
+    stdin: Option<SharedDocument>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(schema: Option<Schema>, files: Vec<PathBuf>) -> Result<Self> {
+        let stdin = match files.iter().any(|f| pipe::is_stdin(f)) {
+            true => {
+                let matrix = pipe::read_stdin()?;
+                pipe::reopen_tty()?;
+                Some(Document::new(matrix).schema(schema.clone()).shared())
+            }
+            false => None,
+        };
+
         let (tx, rx) = unbounded_channel();
-        let page = Box::new(Home::new(tx.clone()));
-        App { should_quit: false, tx, rx: Some(rx), page }
+        let page =
+            Box::new(Home::new(tx.clone(), schema, &files, stdin.clone())?);
+        Ok(App { should_quit: false, tx, rx: Some(rx), page, stdin })
+    }
+
+    /// The table read from stdin as CSV, to be written to stdout on quit.
+    pub fn stdout(&self) -> Option<String> {
+        let doc = self.stdin.as_ref()?.borrow();
+        let matrix = if doc.save_values { &doc.computed } else { &doc.matrix };
+        Some(csv::to_string(matrix))
+    }
+
+    /// Start on a diff of two files instead of the table.
+    pub fn diff(
+        &mut self,
+        left: PathBuf,
+        right: PathBuf,
+        key: Option<&str>,
+    ) -> Result<()> {
+        let diff = Diff::new(
+            self.tx.clone(),
+            csv::load(&left)?,
+            csv::load(&right)?,
+            key,
+        )?
+        .path(Side::Left, Some(left))
+        .path(Side::Right, Some(right))
+        .quit_on_close(true);
+        self.page = Box::new(diff);
+        Ok(())
     }
 
     pub async fn run(&mut self) -> Result<()> {
         // ratatui terminal
-        let mut tui = Tui::new()?.tick_rate(4.0).frame_rate(60.0);
+        let mut tui = Tui::new()?.tick_rate(4.0).frame_rate(60.0).mouse(true);
         tui.run()?;
 
         let tx = self.tx.clone();
@@ -34,7 +85,10 @@ impl App {
         loop {
             // block until the next event
             let e = tui.next().await?;
-            let a = self.page.get_action(e);
+            let a = match e {
+                Event::Key(key) if is_suspend(key) => Action::Suspend,
+                e => self.page.get_action(e),
+            };
             tx.send(a)?;
 
             while let Ok(action) = rx.try_recv() {
@@ -46,12 +100,22 @@ impl App {
                     })?;
                 }
 
-                if let Action::Quit = action {
-                    self.should_quit = true;
+                if let Action::Resize(w, h) = action {
+                    tui.resize(Rect::new(0, 0, w, h))?;
+                    tui.draw(|f| {
+                        self.page.draw(f);
+                    })?;
                 }
 
-                if let Action::ChangePage(component) = action {
-                    self.handle_change_component(component)?
+                if let Action::Suspend = action {
+                    tui.suspend()?;
+                    // back from `fg`, the screen was left by the shell
+                    tui.resume()?;
+                    tui.clear()?;
+                }
+
+                if let Action::Quit = action {
+                    self.should_quit = true;
                 }
             }
 
@@ -63,14 +127,9 @@ impl App {
 
         Ok(())
     }
+}
 
-    /// Handle the logic to render another component on the screen, after
-    /// receiving an [`Action::ChangePage`]
-    fn handle_change_component(&mut self, page: action::Page) -> Result<()> {
-        self.page = match page {
-            action::Page::Home => Box::new(Home::new(self.tx.clone())),
-            action::Page::Details => Box::new(Details::new(self.tx.clone())),
-        };
-        Ok(())
-    }
+/// `Ctrl-Z` suspends the app, like any other program on the terminal.
+fn is_suspend(key: KeyEvent) -> bool {
+    key.code == KeyCode::Char('z') && key.modifiers == KeyModifiers::CONTROL
 }
@@ -0,0 +1,220 @@
+use std::{io::Write, path::Path};
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    cli::{Commands, Output},
+    csv,
+    document::Document,
+    export,
+    file::{self, FileFormat},
+    pipe,
+    schema::Schema,
+    utils,
+};
+
+/// Exit code when the subcommand did what it was asked.
+pub const SUCCESS: u8 = 0;
+/// Exit code when the table does not fit the schema, nothing is written.
+pub const INVALID: u8 = 3;
+/// Exit code when the row or cell asked for is not on the table.
+pub const NOT_FOUND: u8 = 4;
+
+/// Run a subcommand without the TUI and return its exit code. Errors, like a
+/// file that can not be read or an unknown column, exit with 1.
+pub fn run(command: Commands, schema: Option<Schema>) -> Result<u8> {
+    match command {
+        Commands::Diff { .. } => Err(eyre!("diff needs the terminal")),
+        Commands::Get { file, row, column, raw } => {
+            let doc = open(&file, schema)?;
+            let col = column_of(&doc, &column)?;
+            let matrix = if raw { &doc.matrix } else { &doc.computed };
+            match row_index(row).and_then(|r| matrix.get(r)?.get(col)) {
+                Some(value) => {
+                    println!("{value}");
+                    Ok(SUCCESS)
+                }
+                None => not_found(format!("No cell {column} on row {row}")),
+            }
+        }
+        Commands::Set { file, row, column, value, output } => {
+            let mut doc = open(&file, schema)?;
+            let col = column_of(&doc, &column)?;
+            let Some(cell) = row_index(row)
+                .map(|r| (r, col))
+                .filter(|cell| doc.get(*cell).is_some())
+            else {
+                return not_found(format!("No cell {column} on row {row}"));
+            };
+            doc.set_cell(cell, value);
+            write(&mut doc, &file, output)
+        }
+        Commands::DeleteRow { file, row, values, output } => {
+            let mut doc = open(&file, schema)?;
+            freeze(&mut doc, values)?;
+            match row_index(row).filter(|r| *r < doc.matrix.len()) {
+                Some(r) => doc.remove_rows(&[r]),
+                None => return not_found(format!("No row {row}")),
+            }
+            write(&mut doc, &file, output)
+        }
+        Commands::Sort { file, column, desc, values, output } => {
+            let mut doc = open(&file, schema)?;
+            freeze(&mut doc, values)?;
+            let col = column_of(&doc, &column)?;
+            doc.sort(col, desc);
+            write(&mut doc, &file, output)
+        }
+        Commands::Filter { file, expr, values, output } => {
+            let mut doc = open(&file, schema)?;
+            freeze(&mut doc, values)?;
+            let keep = doc.filter(&expr)?;
+            let start = if doc.has_header { 1 } else { 0 };
+            let rows: Vec<usize> = (start..doc.matrix.len())
+                .filter(|r| !keep.contains(r))
+                .collect();
+            doc.remove_rows(&rows);
+            write(&mut doc, &file, output)
+        }
+        Commands::Validate { file } => {
+            if schema.is_none() {
+                return Err(eyre!("validate needs a schema, pass --schema"));
+            }
+            let doc = open(&file, schema)?;
+            report(&doc);
+            Ok(if doc.violations.is_empty() { SUCCESS } else { INVALID })
+        }
+        Commands::Export { file, format, output } => {
+            let doc = open(&file, schema)?;
+            let table = export_table(&doc, &file);
+            match output.filter(|o| !pipe::is_stdin(o)) {
+                Some(path) => export::save(&path, &table, format)?,
+                None => {
+                    print!(
+                        "{}",
+                        export::render(&table, format.unwrap_or_default())
+                    )
+                }
+            }
+            Ok(SUCCESS)
+        }
+        Commands::Convert { input, output, values } => {
+            let doc = open(&input, schema)?;
+            let format = doc.format.to(&output);
+            let matrix = if values { &doc.computed } else { &doc.matrix };
+            match export::Format::from_path(&output) {
+                None if pipe::is_stdin(&output) => {
+                    stdout(csv::to_string(matrix).as_bytes())?;
+                }
+                // JSON written back as JSON keeps its shape
+                Some(_)
+                    if doc.format.shape().is_some()
+                        && format.shape().is_some() =>
+                {
+                    utils::write_atomic(&output, doc.contents_as(&format)?)?;
+                }
+                None if let FileFormat::Xlsx(_)
+                | FileFormat::Parquet(_)
+                | FileFormat::Arrow(_)
+                | FileFormat::Fixed(_) = format =>
+                {
+                    utils::write_atomic(&output, doc.contents_as(&format)?)?;
+                }
+                // the other formats have no formulas
+                Some(format) if format != export::Format::Csv => {
+                    let table = export_table(&doc, &input);
+                    export::save(&output, &table, Some(format))?;
+                }
+                _ => csv::save(&output, matrix)?,
+            }
+            Ok(SUCCESS)
+        }
+    }
+}
+
+/// Read a file, or stdin for `-`, into a document that is not saved on
+/// changes, it is written once at the end.
+fn open(path: &Path, schema: Option<Schema>) -> Result<Document> {
+    let (matrix, format) = match pipe::is_stdin(path) {
+        true => (pipe::read_stdin()?, FileFormat::Csv),
+        false => file::load(path, schema.as_ref())?,
+    };
+    Ok(Document::new(matrix).format(format).schema(schema))
+}
+
+/// The whole table, named after the file it was read from, as the document
+/// has no path to take the name from.
+fn export_table(doc: &Document, path: &Path) -> export::Table {
+    let mut table = export::Table::of(doc, None);
+    match (&doc.format, path.file_stem()) {
+        (FileFormat::Sqlite(_), _) => {}
+        (_, Some(stem)) if !pipe::is_stdin(path) => {
+            table.name = stem.to_string_lossy().into_owned();
+        }
+        _ => {}
+    }
+    table
+}
+
+/// Before moving rows, replace the formulas by their results when `values`,
+/// or refuse, as the references of the formulas would point to other rows.
+fn freeze(doc: &mut Document, values: bool) -> Result<()> {
+    match (doc.formulas.is_empty(), values) {
+        (true, _) => Ok(()),
+        (false, true) => {
+            doc.replace_matrix(doc.computed.clone());
+            Ok(())
+        }
+        (false, false) => Err(eyre!(
+            "The table has formulas, their references would point to other \
+             rows, write their results with --values"
+        )),
+    }
+}
+
+fn column_of(doc: &Document, name: &str) -> Result<usize> {
+    doc.column_by_name(name).ok_or(eyre!("Unknown column {name}"))
+}
+
+/// The index on the matrix of a row number, which starts at 1.
+fn row_index(row: usize) -> Option<usize> {
+    row.checked_sub(1)
+}
+
+fn not_found(message: String) -> Result<u8> {
+    eprintln!("{message}");
+    Ok(NOT_FOUND)
+}
+
+/// Print the violations of the schema, their messages name the cells.
+fn report(doc: &Document) {
+    for v in &doc.violations {
+        eprintln!("{}", v.message);
+    }
+}
+
+/// Write the changed document to the file, or where `output` says, unless
+/// it does not fit the schema.
+fn write(doc: &mut Document, file: &Path, output: Output) -> Result<u8> {
+    if !doc.violations.is_empty() && !output.force {
+        report(doc);
+        eprintln!(
+            "Not written, the table does not fit the schema, use --force"
+        );
+        return Ok(INVALID);
+    }
+    match output.output {
+        Some(path) if pipe::is_stdin(&path) => stdout(&doc.contents()?)?,
+        Some(path) => doc.save_as(&path)?,
+        None if pipe::is_stdin(file) => stdout(&doc.contents()?)?,
+        None if let FileFormat::Sqlite(_) = doc.format => {
+            doc.write_table(file)?
+        }
+        None => utils::write_atomic(file, doc.contents()?)?,
+    }
+    Ok(SUCCESS)
+}
+
+fn stdout(contents: &[u8]) -> Result<()> {
+    std::io::stdout().write_all(contents).wrap_err("Could not write to stdout")
+}
@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+
+use clap::{Args as ClapArgs, Parser, Subcommand};
+
+use crate::export::Format;
+
+/// Edit CSV files on the terminal.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// CSV files to open, on a buffer each.
+    pub files: Vec<PathBuf>,
+
+    /// JSON5 schema that the table is validated against. Changes are not
+    /// saved while the table is invalid, unless forced with `W`.
+    #[arg(long)]
+    pub schema: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Compare two CSV files side by side.
+    Diff {
+        /// The file before the changes.
+        left: PathBuf,
+        /// The file after the changes.
+        right: PathBuf,
+        /// Line up the rows by this column, a name on the header or a
+        /// letter, instead of by their position.
+        #[arg(long)]
+        key: Option<String>,
+    },
+    /// Print the value of a cell, the result when it is a formula.
+    Get {
+        file: PathBuf,
+        /// Row number, starting at 1 with the header.
+        row: usize,
+        /// A name on the header or a letter.
+        column: String,
+        /// Print the formula instead of its result.
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Change the value of a cell.
+    Set {
+        file: PathBuf,
+        /// Row number, starting at 1 with the header.
+        row: usize,
+        /// A name on the header or a letter.
+        column: String,
+        value: String,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Remove a row.
+    DeleteRow {
+        file: PathBuf,
+        /// Row number, starting at 1 with the header.
+        row: usize,
+        /// Write the results of the formulas instead of the formulas, which
+        /// are refused otherwise, as their references would point to other
+        /// rows.
+        #[arg(long)]
+        values: bool,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Sort the rows below the header by a column, as numbers when they are.
+    Sort {
+        file: PathBuf,
+        /// A name on the header or a letter.
+        column: String,
+        /// From the largest value to the smallest.
+        #[arg(long)]
+        desc: bool,
+        /// Write the results of the formulas instead of the formulas, which
+        /// are refused otherwise, as their references would point to other
+        /// rows.
+        #[arg(long)]
+        values: bool,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Keep the rows where an expression over the columns is true, like
+    /// `"qty > 0"`.
+    Filter {
+        file: PathBuf,
+        expr: String,
+        /// Write the results of the formulas instead of the formulas, which
+        /// are refused otherwise, as their references would point to other
+        /// rows.
+        #[arg(long)]
+        values: bool,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Check a file against the schema given with `--schema`, printing what
+    /// it breaks.
+    Validate { file: PathBuf },
+    /// Print the table as CSV, JSON, NDJSON, Markdown or HTML, with the
+    /// results of the formulas.
+    Export {
+        file: PathBuf,
+        /// The format, by the extension of the output when not given, or CSV.
+        #[arg(short, long)]
+        format: Option<Format>,
+        /// Write to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write a file to another one, with formulas computed when asked. The
+    /// format is the extension of the output, like `export`.
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        /// Write the results of the formulas instead of the formulas.
+        #[arg(long)]
+        values: bool,
+    },
+}
+
+/// Where the subcommands that change a file write it.
+#[derive(ClapArgs, Debug)]
+pub struct Output {
+    /// Write the result to this file, or to stdout with `-`, instead of
+    /// changing the file.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Write the result even if it does not fit the schema.
+    #[arg(long)]
+    pub force: bool,
+}
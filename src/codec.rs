@@ -0,0 +1,140 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{Result, WrapErr};
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+
+/// A compression a file is stored with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    /// The compression of a file by its extension.
+    pub fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Codec::Gzip),
+            "zst" | "zstd" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The compression of a file by its extension, or by its first bytes
+    /// when it has another extension, so a file is written back compressed
+    /// like it was read.
+    pub fn detect(path: &Path) -> Option<Self> {
+        if let Some(codec) = Self::of(path) {
+            return Some(codec);
+        }
+        let mut magic = [0; 4];
+        let mut file = File::open(path).ok()?;
+        let read = file.read(&mut magic).ok()?;
+        match &magic[..read] {
+            [0x1f, 0x8b, ..] => Some(Codec::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd] => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// The path without the extension of its compression, like `data.csv` for
+/// `data.csv.gz`, to tell the format of what was compressed.
+pub fn strip(path: &Path) -> PathBuf {
+    match Codec::of(path) {
+        Some(_) => path.with_extension(""),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Read a text file, decompressed as it is read when it is compressed.
+pub fn read_to_string(path: &Path) -> Result<String> {
+    let read = || -> std::io::Result<String> {
+        let file = BufReader::new(File::open(path)?);
+        let mut reader: Box<dyn Read> = match Codec::detect(path) {
+            Some(Codec::Gzip) => Box::new(MultiGzDecoder::new(file)),
+            Some(Codec::Zstd) => Box::new(zstd::Decoder::with_buffer(file)?),
+            None => Box::new(file),
+        };
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Ok(text)
+    };
+    read().wrap_err_with(|| format!("Could not read {}", path.display()))
+}
+
+/// The contents to write to a file, compressed like the file is or as its
+/// extension says.
+pub fn encode<'a>(path: &Path, contents: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+    let encoded = match Codec::detect(path) {
+        Some(Codec::Gzip) => {
+            let mut encoder = GzEncoder::new(Vec::new(), Default::default());
+            encoder.write_all(contents).and_then(|_| encoder.finish())
+        }
+        Some(Codec::Zstd) => zstd::encode_all(contents, 0),
+        None => return Ok(Cow::Borrowed(contents)),
+    };
+    let encoded = encoded
+        .wrap_err_with(|| format!("Could not compress {}", path.display()))?;
+    Ok(Cow::Owned(encoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("codec-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn codec_by_extension() {
+        assert_eq!(Codec::of(Path::new("a.csv.GZ")), Some(Codec::Gzip));
+        assert_eq!(Codec::of(Path::new("a.csv.zst")), Some(Codec::Zstd));
+        assert_eq!(Codec::of(Path::new("a.csv")), None);
+        assert_eq!(strip(Path::new("dir/a.csv.gz")), Path::new("dir/a.csv"));
+        assert_eq!(strip(Path::new("a.csv")), Path::new("a.csv"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "a,b\n1,ü\n";
+        for name in ["data.csv.gz", "data.csv.zst", "data.csv"] {
+            let path = temp(name);
+            let encoded = encode(&path, text.as_bytes()).unwrap();
+            assert_eq!(
+                matches!(encoded, Cow::Borrowed(_)),
+                name == "data.csv",
+                "{name}"
+            );
+            std::fs::write(&path, encoded).unwrap();
+            assert_eq!(read_to_string(&path).unwrap(), text, "{name}");
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn detect_by_contents() {
+        let path = temp("data.csv");
+        let gzip = encode(Path::new("x.gz"), b"a\n").unwrap();
+        std::fs::write(&path, &gzip).unwrap();
+        assert_eq!(Codec::detect(&path), Some(Codec::Gzip));
+        assert_eq!(read_to_string(&path).unwrap(), "a\n");
+
+        // written back compressed like it was read
+        let encoded = encode(&path, b"b\n").unwrap();
+        std::fs::write(&path, encoded).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "b\n");
+
+        std::fs::write(&path, "plain").unwrap();
+        assert_eq!(Codec::detect(&path), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::Path,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, ArrayRef, RecordBatch, StringArray},
+    compute::{cast_with_options, CastOptions},
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    ipc::{
+        reader::{FileReader, StreamReader},
+        writer::FileWriter,
+    },
+    util::display::{ArrayFormatter, FormatOptions},
+};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+
+use crate::types::ColumnType;
+
+/// The schema of the Parquet or Arrow file a table was read from, so it is
+/// written back with the same column types.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    pub schema: Option<SchemaRef>,
+    /// How the columns of a Parquet file were compressed.
+    pub compression: Option<Compression>,
+    /// The rows, by their id on the document, that were null on each text
+    /// column, as an empty cell of text may also be an empty string.
+    pub nulls: HashMap<String, HashSet<usize>>,
+}
+
+impl Layout {
+    /// The same column types and nulls, for a file of another format.
+    pub fn schema_only(&self) -> Self {
+        Self { compression: None, ..self.clone() }
+    }
+
+    /// The first column with nested or binary values, which can not be
+    /// written back from the way they are shown.
+    pub fn nested_column(&self) -> Option<&str> {
+        let schema = self.schema.as_ref()?;
+        schema
+            .fields()
+            .iter()
+            .find(|f| !from_text(f.data_type()))
+            .map(|f| f.name().as_str())
+    }
+}
+
+/// Read a Parquet file, with the names of the columns as header and every
+/// value as it is written in CSV. Nulls are empty cells.
+pub fn load_parquet(path: &Path) -> Result<(Vec<Vec<String>>, Layout)> {
+    let read = || -> Result<_> {
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+        let compression = builder
+            .metadata()
+            .row_groups()
+            .first()
+            .and_then(|group| group.columns().first())
+            .map(|column| column.compression());
+        let schema = builder.schema().clone();
+        let batches = builder.build()?.collect::<Result<Vec<_>, _>>()?;
+        Ok((
+            matrix(&schema, &batches)?,
+            Layout {
+                nulls: nulls(&schema, &batches),
+                schema: Some(schema),
+                compression,
+            },
+        ))
+    };
+    read().wrap_err_with(|| format!("Could not read {}", path.display()))
+}
+
+/// Read an Arrow IPC file, or stream, like a Parquet file.
+pub fn load_arrow(path: &Path) -> Result<(Vec<Vec<String>>, Layout)> {
+    let read = || -> Result<_> {
+        let (schema, batches) =
+            match FileReader::try_new(File::open(path)?, None) {
+                Ok(reader) => {
+                    (reader.schema(), reader.collect::<Result<Vec<_>, _>>()?)
+                }
+                Err(_) => {
+                    let reader =
+                        StreamReader::try_new(File::open(path)?, None)?;
+                    (reader.schema(), reader.collect::<Result<Vec<_>, _>>()?)
+                }
+            };
+        Ok((
+            matrix(&schema, &batches)?,
+            Layout {
+                nulls: nulls(&schema, &batches),
+                schema: Some(schema),
+                compression: None,
+            },
+        ))
+    };
+    read().wrap_err_with(|| format!("Could not read {}", path.display()))
+}
+
+/// Write the table as a Parquet file, compressed like the file it was read
+/// from, or with Snappy. `ids` are the ids of the rows on the document.
+pub fn to_parquet(
+    layout: &Layout,
+    columns: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+    ids: &[usize],
+) -> Result<Vec<u8>> {
+    let batch = batch(layout, columns, types, rows, ids)?;
+    let properties = WriterProperties::builder()
+        .set_compression(layout.compression.unwrap_or(Compression::SNAPPY))
+        .build();
+    let mut bytes = Vec::new();
+    let mut writer =
+        ArrowWriter::try_new(&mut bytes, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close().wrap_err("Could not write the Parquet file")?;
+    Ok(bytes)
+}
+
+/// Write the table as an Arrow IPC file.
+pub fn to_arrow(
+    layout: &Layout,
+    columns: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+    ids: &[usize],
+) -> Result<Vec<u8>> {
+    let batch = batch(layout, columns, types, rows, ids)?;
+    let mut bytes = Vec::new();
+    let mut writer = FileWriter::try_new(&mut bytes, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish().wrap_err("Could not write the Arrow file")?;
+    Ok(bytes)
+}
+
+fn matrix(
+    schema: &Schema,
+    batches: &[RecordBatch],
+) -> Result<Vec<Vec<String>>> {
+    let header = schema.fields().iter().map(|f| f.name().clone()).collect();
+    let options = FormatOptions::default();
+    let mut matrix = vec![header];
+    for batch in batches {
+        let formatters = batch
+            .columns()
+            .iter()
+            .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()?;
+        for r in 0..batch.num_rows() {
+            let row = batch
+                .columns()
+                .iter()
+                .zip(&formatters)
+                .map(|(column, formatter)| match column.is_null(r) {
+                    true => String::new(),
+                    false => formatter.value(r).to_string(),
+                })
+                .collect();
+            matrix.push(row);
+        }
+    }
+    Ok(matrix)
+}
+
+/// The rows of the text columns that are null, by their id on the document,
+/// the first row after the header being 1.
+fn nulls(
+    schema: &Schema,
+    batches: &[RecordBatch],
+) -> HashMap<String, HashSet<usize>> {
+    let mut nulls: HashMap<String, HashSet<usize>> = HashMap::new();
+    let mut id = 1;
+    for batch in batches {
+        for (field, column) in schema.fields().iter().zip(batch.columns()) {
+            if !is_text(field.data_type()) {
+                continue;
+            }
+            let rows = (0..batch.num_rows())
+                .filter(|r| column.is_null(*r))
+                .map(|r| id + r);
+            nulls.entry(field.name().clone()).or_default().extend(rows);
+        }
+        id += batch.num_rows();
+    }
+    nulls
+}
+
+/// The rows as a record batch. Columns of the schema keep their type, and
+/// the other columns get the type they were inferred as. Empty cells are
+/// nulls, but on text columns only where they were null when read.
+fn batch(
+    layout: &Layout,
+    columns: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+    ids: &[usize],
+) -> Result<RecordBatch> {
+    let options = CastOptions { safe: false, ..Default::default() };
+    let mut fields = Vec::new();
+    let mut arrays: Vec<ArrayRef> = Vec::new();
+    for (c, name) in columns.iter().enumerate() {
+        let field = layout
+            .schema
+            .as_ref()
+            .and_then(|s| s.field_with_name(name).ok().cloned())
+            .unwrap_or_else(|| {
+                let t = types.get(c).copied().unwrap_or_default();
+                Field::new(name, data_type(t), true)
+            });
+        if !from_text(field.data_type()) {
+            return Err(eyre!(
+                "Column {name} has nested or binary values, they can not be \
+                 written back"
+            ));
+        }
+        // text is kept as it is, empty strings and spaces too, and is only
+        // null where it was, other values are read without spaces
+        let text = is_text(field.data_type());
+        let nulls = layout.nulls.get(name);
+        let was_null = |r: usize| {
+            ids.get(r).zip(nulls).is_some_and(|(id, nulls)| nulls.contains(id))
+        };
+        let values = rows.iter().enumerate().map(|(r, row)| {
+            let value = row.get(c).map_or("", |v| v.as_str());
+            match text {
+                true if value.is_empty() && was_null(r) => None,
+                true => Some(value),
+                false => Some(value.trim()).filter(|v| !v.is_empty()),
+            }
+        });
+        let array = cast_with_options(
+            &StringArray::from_iter(values),
+            field.data_type(),
+            &options,
+        )
+        .wrap_err_with(|| {
+            format!("Column {name} can not be written as {}", field.data_type())
+        })?;
+        fields.push(field);
+        arrays.push(array);
+    }
+
+    let metadata = layout
+        .schema
+        .as_ref()
+        .map(|s| s.metadata().clone())
+        .unwrap_or_default();
+    let schema = Schema::new_with_metadata(fields, metadata);
+    RecordBatch::try_new(Arc::new(schema), arrays)
+        .wrap_err("Could not write the table")
+}
+
+/// If the values of a type are text, shown as they are.
+fn is_text(t: &DataType) -> bool {
+    match t {
+        DataType::Dictionary(_, values) => is_text(values),
+        t => matches!(
+            t,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+        ),
+    }
+}
+
+/// If the values of a type are read back from the way they are shown,
+/// nested and binary values are not.
+fn from_text(t: &DataType) -> bool {
+    match t {
+        DataType::Dictionary(_, values) => from_text(values),
+        t => {
+            !t.is_nested()
+                && !matches!(
+                    t,
+                    DataType::Binary
+                        | DataType::LargeBinary
+                        | DataType::BinaryView
+                        | DataType::FixedSizeBinary(_)
+                )
+        }
+    }
+}
+
+fn data_type(t: ColumnType) -> DataType {
+    match t {
+        ColumnType::Integer => DataType::Int64,
+        ColumnType::Float => DataType::Float64,
+        ColumnType::Boolean => DataType::Boolean,
+        ColumnType::Date => DataType::Date32,
+        ColumnType::DateTime => {
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        }
+        ColumnType::Empty | ColumnType::Email | ColumnType::Text => {
+            DataType::Utf8
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Int64Array;
+
+    use super::*;
+
+    /// Write a batch to a Parquet file and read it back as a table.
+    fn load(batch: &RecordBatch) -> (Vec<Vec<String>>, Layout) {
+        let path = std::env::temp_dir()
+            .join(format!("columnar-{}.parquet", std::process::id()));
+        let mut bytes = Vec::new();
+        let mut writer =
+            ArrowWriter::try_new(&mut bytes, batch.schema(), None).unwrap();
+        writer.write(batch).unwrap();
+        writer.close().unwrap();
+        std::fs::write(&path, bytes).unwrap();
+        let loaded = load_parquet(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn empty_strings_and_nulls_round_trip() {
+        let text: StringArray =
+            vec![Some(""), None, Some("  "), Some("a")].into_iter().collect();
+        let n = Int64Array::from(vec![Some(1), None, Some(3), None]);
+        let original = RecordBatch::try_from_iter([
+            ("text", Arc::new(text) as ArrayRef),
+            ("n", Arc::new(n) as ArrayRef),
+        ])
+        .unwrap();
+
+        let (matrix, layout) = load(&original);
+        assert_eq!(matrix[1], ["", "1"]);
+        assert_eq!(matrix[2], ["", ""]);
+        let ids: Vec<usize> = (1..matrix.len()).collect();
+        let written =
+            batch(&layout, &matrix[0], &[], &matrix[1..], &ids).unwrap();
+        assert_eq!(written, original);
+    }
+
+    #[test]
+    fn new_rows_keep_empty_text() {
+        let text: StringArray = vec![None::<&str>].into_iter().collect();
+        let original =
+            RecordBatch::try_from_iter([("text", Arc::new(text) as ArrayRef)])
+                .unwrap();
+        let (matrix, layout) = load(&original);
+        let rows = vec![vec![String::new()], vec![String::new()]];
+        let written = batch(&layout, &matrix[0], &[], &rows, &[1, 5]).unwrap();
+        let column = written.column(0);
+        assert!(column.is_null(0));
+        assert!(!column.is_null(1));
+    }
+}
@@ -0,0 +1,219 @@
+use std::{path::PathBuf, str::FromStr};
+
+use color_eyre::eyre::{eyre, Report, Result};
+
+use crate::{dedupe, export::Format, join::JoinKind};
+
+/// A command typed on the command line, after `:`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// `:addcol [--live] <name> = <expression>`, append a column computed
+    /// row by row from an expression over the other columns. A live column
+    /// is stored as formulas, so it is computed again on every change.
+    AddColumn { name: String, expr: String, live: bool },
+    /// `:sql <query>`, run a query over the table, named `this`, and show
+    /// the result.
+    Sql { query: String },
+    /// `:pivot`, summarise the table on a pivot table.
+    Pivot,
+    /// `:dedupe [-i] [-w] [<column>...]`, find the rows with the same values
+    /// on the key columns, or on the whole row, and pick which ones to keep.
+    /// `-i` ignores case and `-w` whitespace.
+    Dedupe { keys: Vec<String>, options: dedupe::Options },
+    /// `:diff [--key <column>] <file>`, compare the table with a CSV file,
+    /// lining up the rows by the key column or by their position.
+    Diff { path: PathBuf, key: Option<String> },
+    /// `:join [--inner|--left|--outer] <file> <keys>[=<other keys>]
+    /// [<column>...]`, append the columns of another CSV file, matching the
+    /// rows by the key columns, separated by commas. Without columns, every
+    /// column but the keys is appended.
+    Join {
+        path: PathBuf,
+        kind: JoinKind,
+        keys: Vec<String>,
+        other_keys: Option<Vec<String>>,
+        columns: Vec<String>,
+    },
+    /// `:e <file>`, open a file on a new buffer, or go to its buffer if
+    /// it is already open.
+    Edit { path: PathBuf },
+    /// `:bn`, go to the next buffer.
+    NextBuffer,
+    /// `:bp`, go to the previous buffer.
+    PrevBuffer,
+    /// `:bd[!]`, close the buffer, `!` closes it even with changes not
+    /// saved.
+    CloseBuffer { force: bool },
+    /// `:sp [<file>]`, split the buffer with another view of the table, or
+    /// of a file, one above the other. `:vsp` puts them side by side.
+    Split { path: Option<PathBuf>, vertical: bool },
+    /// `:export [--format <format>] <file>`, write the table, or the selected
+    /// cells, as CSV, JSON, NDJSON, Markdown, HTML or a SQLite table, by the
+    /// extension of the file unless the format is given.
+    Export { path: PathBuf, format: Option<Format> },
+    /// `:w [<file>]`, save the table, even if it is not valid, or save it
+    /// to another file from now on, in the format of its extension: CSV,
+    /// JSON, NDJSON, XLSX, a SQLite table, Parquet, Arrow or fixed width,
+    /// compressed with gzip or zstd when it ends in `.gz` or `.zst`.
+    Write { path: Option<PathBuf> },
+    /// `:sheet [<name>]`, show another sheet of the workbook of the table, or
+    /// `:table` another table of its database, picked from a list when no
+    /// name is given.
+    Sheet { name: Option<String> },
+    /// `:close[!]`, close the focused view of the buffer, `!` closes it even
+    /// when it is the last view of a table with changes not saved.
+    CloseView { force: bool },
+    /// `:only[!]`, close every view of the buffer but the focused one, the
+    /// same way.
+    OnlyView { force: bool },
+    /// `:q[!]`, quit, `!` quits even with changes not saved.
+    Quit { force: bool },
+}
+
+impl FromStr for Command {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (command, args) =
+            s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let args = args.trim();
+
+        match command {
+            "addcol" => {
+                let (live, args) = match args.strip_prefix("--live") {
+                    Some(args) => (true, args.trim_start()),
+                    None => (false, args),
+                };
+                let (name, expr) = args
+                    .split_once('=')
+                    .ok_or(eyre!("Usage: addcol [--live] <name> = <expr>"))?;
+                let name = name.trim().to_string();
+                if name.is_empty() {
+                    return Err(eyre!("The column needs a name"));
+                }
+                Ok(Command::AddColumn {
+                    name,
+                    expr: expr.trim().to_string(),
+                    live,
+                })
+            }
+            "sql" if args.is_empty() => Err(eyre!("Usage: sql <query>")),
+            "sql" => Ok(Command::Sql { query: args.to_string() }),
+            "pivot" => Ok(Command::Pivot),
+            "dedupe" => {
+                let mut options = dedupe::Options::default();
+                let mut keys = Vec::new();
+                for arg in args.split_whitespace() {
+                    match arg {
+                        "-i" => options.ignore_case = true,
+                        "-w" => options.ignore_space = true,
+                        _ if arg.starts_with('-') => {
+                            return Err(eyre!("Unknown option {arg}"));
+                        }
+                        _ => keys.push(arg.to_string()),
+                    }
+                }
+                Ok(Command::Dedupe { keys, options })
+            }
+            "diff" => {
+                let (key, path) = match args.strip_prefix("--key") {
+                    Some(args) => {
+                        let (key, path) = args
+                            .trim_start()
+                            .split_once(char::is_whitespace)
+                            .ok_or(eyre!(
+                                "Usage: diff [--key <column>] <file>"
+                            ))?;
+                        (Some(key.to_string()), path.trim())
+                    }
+                    None => (None, args),
+                };
+                if path.is_empty() {
+                    return Err(eyre!("Usage: diff [--key <column>] <file>"));
+                }
+                Ok(Command::Diff { path: path.into(), key })
+            }
+            "join" => {
+                let usage = "Usage: join [--inner|--left|--outer] <file> \
+                             <keys>[=<other keys>] [<column>...]";
+                let mut args = args.split_whitespace().peekable();
+                let kind = match args.peek() {
+                    Some(&"--inner") => JoinKind::Inner,
+                    Some(&"--left") => JoinKind::Left,
+                    Some(&"--outer") => JoinKind::Outer,
+                    Some(arg) if arg.starts_with("--") => {
+                        return Err(eyre!("Unknown option {arg}"));
+                    }
+                    _ => JoinKind::default(),
+                };
+                if args.peek().is_some_and(|arg| arg.starts_with("--")) {
+                    args.next();
+                }
+                let path = args.next().ok_or(eyre!(usage))?;
+                let keys = args.next().ok_or(eyre!(usage))?;
+                let list = |keys: &str| -> Vec<String> {
+                    keys.split(',').map(|k| k.trim().to_string()).collect()
+                };
+                let (keys, other_keys) = match keys.split_once('=') {
+                    Some((keys, other)) => (list(keys), Some(list(other))),
+                    None => (list(keys), None),
+                };
+                if other_keys.as_ref().is_some_and(|o| o.len() != keys.len()) {
+                    return Err(eyre!(
+                        "The file needs as many keys as the table, {}",
+                        keys.len()
+                    ));
+                }
+                Ok(Command::Join {
+                    path: path.into(),
+                    kind,
+                    keys,
+                    other_keys,
+                    columns: args.map(|c| c.to_string()).collect(),
+                })
+            }
+            "e" | "edit" if args.is_empty() => Err(eyre!("Usage: e <file>")),
+            "e" | "edit" => Ok(Command::Edit { path: args.into() }),
+            "bn" | "bnext" => Ok(Command::NextBuffer),
+            "bp" | "bprev" => Ok(Command::PrevBuffer),
+            "bd" | "bdelete" => Ok(Command::CloseBuffer { force: false }),
+            "bd!" | "bdelete!" => Ok(Command::CloseBuffer { force: true }),
+            "sp" | "split" | "vsp" | "vsplit" => Ok(Command::Split {
+                path: Some(args).filter(|a| !a.is_empty()).map(Into::into),
+                vertical: command.starts_with('v'),
+            }),
+            "export" => {
+                let usage = "Usage: export [--format <format>] <file>";
+                let (format, path) = match args.strip_prefix("--format") {
+                    Some(args) => {
+                        let (format, path) = args
+                            .trim_start()
+                            .split_once(char::is_whitespace)
+                            .ok_or(eyre!(usage))?;
+                        (Some(format.parse()?), path.trim())
+                    }
+                    None => (None, args),
+                };
+                if path.is_empty() {
+                    return Err(eyre!(usage));
+                }
+                Ok(Command::Export { path: path.into(), format })
+            }
+            "w" | "write" => Ok(Command::Write {
+                path: Some(args).filter(|a| !a.is_empty()).map(Into::into),
+            }),
+            "sheet" | "table" => Ok(Command::Sheet {
+                name: Some(args).filter(|a| !a.is_empty()).map(Into::into),
+            }),
+            "close" => Ok(Command::CloseView { force: false }),
+            "close!" => Ok(Command::CloseView { force: true }),
+            "only" => Ok(Command::OnlyView { force: false }),
+            "only!" => Ok(Command::OnlyView { force: true }),
+            "q" | "quit" => Ok(Command::Quit { force: false }),
+            "q!" | "quit!" => Ok(Command::Quit { force: true }),
+            "" => Err(eyre!("Empty command")),
+            _ => Err(eyre!("Unknown command {command}")),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::cmp::Reverse;
+
+/// How a candidate matched the query, the lower the better.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Match {
+    Prefix,
+    /// All chars of the query appear in order in the candidate. Holds the
+    /// number of chars skipped between them.
+    Fuzzy(usize),
+}
+
+/// Match `query` against `candidate`, ignoring case.
+fn matches(candidate: &str, query: &str) -> Option<Match> {
+    let candidate = candidate.to_lowercase();
+    let query = query.to_lowercase();
+
+    if candidate.starts_with(&query) {
+        return Some(Match::Prefix);
+    }
+
+    let mut chars = candidate.chars();
+    let mut gaps = 0;
+    for q in query.chars() {
+        loop {
+            let c = chars.next()?;
+            if c == q {
+                break;
+            }
+            gaps += 1;
+        }
+    }
+    Some(Match::Fuzzy(gaps))
+}
+
+/// Rank `candidates`, pairs of (value, frequency), against what the user has
+/// typed so far. Prefix matches come before fuzzy matches, then the most
+/// frequent values win. A candidate equal to the query is not suggested,
+/// since accepting it would change nothing.
+pub fn rank(
+    candidates: &[(String, usize)],
+    query: &str,
+    limit: usize,
+) -> Vec<String> {
+    let mut ranked: Vec<(Match, Reverse<usize>, &str)> = candidates
+        .iter()
+        .filter(|(value, _)| value != query)
+        .filter_map(|(value, freq)| {
+            matches(value, query).map(|m| (m, Reverse(*freq), value.as_str()))
+        })
+        .collect();
+
+    ranked.sort();
+    ranked.into_iter().take(limit).map(|(_, _, v)| v.to_owned()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(values: &[(&str, usize)]) -> Vec<(String, usize)> {
+        values.iter().map(|(v, n)| (v.to_string(), *n)).collect()
+    }
+
+    #[test]
+    fn prefix_before_fuzzy_then_frequency() {
+        let candidates = candidates(&[
+            ("Berlin", 1),
+            ("Bern", 3),
+            ("Lisbon", 9),
+            ("Brno", 2),
+        ]);
+        assert_eq!(rank(&candidates, "ber", 10), ["Bern", "Berlin"]);
+        assert_eq!(
+            rank(&candidates, "bn", 10),
+            ["Brno", "Bern", "Lisbon", "Berlin"]
+        );
+        assert_eq!(rank(&candidates, "", 2), ["Lisbon", "Bern"]);
+    }
+
+    #[test]
+    fn ignores_case_and_the_query_itself() {
+        let candidates = candidates(&[("Oslo", 1), ("oslo", 5), ("Osaka", 1)]);
+        assert_eq!(rank(&candidates, "oslo", 10), ["Oslo"]);
+        assert_eq!(rank(&candidates, "OS", 10), ["oslo", "Osaka", "Oslo"]);
+        assert!(rank(&candidates, "x", 10).is_empty());
+    }
+}
@@ -1,163 +1,562 @@
-use color_eyre::eyre::{eyre, Result};
-use crossterm::event::KeyCode;
-use itertools::Itertools;
+use std::{
+    cell::{Ref, RefMut},
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{
+    KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind,
+};
 use ratatui::{prelude::*, widgets::*, Frame};
-use tokio::{spawn, sync::mpsc, io::AsyncWriteExt};
 
-use crate::{action::Action, utils::centered_rect};
+use crate::{
+    action::Action,
+    document::{Document, SharedDocument},
+    formula::{self, Cell},
+    stats::ColumnStats,
+    utils::{centered_rect, column_name},
+};
 
 use super::{
     input::{Input, Mode},
     Component, HandleActionResponse,
 };
 
+/// Two clicks on the same cell within this time are a double click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// What dragging the mouse does, decided by where the button was pressed.
+#[derive(Clone, Copy, Debug)]
+enum Drag {
+    /// Select the cells from this one to the one under the mouse.
+    Select(Cell),
+    /// Resize this column, the button was pressed on its right border.
+    Resize(usize),
+}
+
+/// A view of a document, with its own cursor and scroll. Many views can show
+/// the same document.
 pub struct CsvTable<'a> {
-    tx: mpsc::UnboundedSender<Action>,
     /// which cell is currently focused. (row, coll)
     pub cell_focused: (usize, usize),
     /// if the component is focused
     pub focused: bool,
-    /// Matrix of rows and cells
-    pub matrix: Vec<Vec<String>>,
+    /// The table that is shown and edited.
+    pub document: SharedDocument,
+    /// Ignore the keys that change the table.
+    pub read_only: bool,
+    /// Background of cells that are marked by the page, like the changes of
+    /// a diff.
+    pub highlights: HashMap<Cell, Color>,
+    /// The first row that is rendered, so the focused row is always visible.
+    offset: usize,
+    /// Where the table was drawn, to find the cell under the mouse.
+    pub area: Rect,
+    /// Where each column was drawn.
+    columns: Vec<Rect>,
+    /// Widths of the columns resized with the mouse.
+    widths: HashMap<usize, u16>,
+    /// The corners of the cells selected by dragging the mouse.
+    pub selection: Option<(Cell, Cell)>,
+    drag: Option<Drag>,
+    /// When and where the last click was, to tell a double click.
+    last_click: Option<(Instant, Cell)>,
     /// If this is Some, a popup will be rendered ontop of the current UI.
     edit_input: Option<Input<'a>>,
     /// this is used to make 'edit_input' into None or Some.
     show_edit_popup: bool,
     show_delete_popup: bool,
+    /// Statistics of the focused column, rendered on a popup when Some.
+    stats: Option<ColumnStats>,
 }
 
 impl<'a> CsvTable<'a> {
-    pub fn new(tx: mpsc::UnboundedSender<Action>) -> Self {
-        let matrix: Vec<Vec<String>> = include_str!("../../testdata.csv")
-            .trim_end()
-            .split("\n")
-            .into_iter()
-            .map(|r| {
-                r.replace("\"", "")
-                    .split(",")
-                    .filter_map(|v| v.parse().ok())
-                    .collect()
-            })
-            .collect();
-
+    pub fn new(document: SharedDocument) -> Self {
         Self {
-            tx,
             focused: true,
             show_edit_popup: false,
             show_delete_popup: false,
+            stats: None,
             edit_input: None,
             cell_focused: (0, 0),
-            matrix,
+            document,
+            read_only: false,
+            highlights: HashMap::new(),
+            offset: 0,
+            area: Rect::default(),
+            columns: Vec::new(),
+            widths: HashMap::new(),
+            selection: None,
+            drag: None,
+            last_click: None,
+        }
+    }
+
+    /// A view of a new document that is not saved anywhere.
+    pub fn from_matrix(matrix: Vec<Vec<String>>) -> Self {
+        Self::new(Document::new(matrix).shared())
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn doc(&self) -> Ref<'_, Document> {
+        self.document.borrow()
+    }
+
+    pub fn doc_mut(&self) -> RefMut<'_, Document> {
+        self.document.borrow_mut()
+    }
+
+    /// If the keys that change the table are handled, the view is not read
+    /// only and neither is the table.
+    pub fn editable(&self) -> bool {
+        !self.read_only && self.doc().read_only().is_none()
+    }
+
+    /// Go back to the document before its last change. Returns false if
+    /// there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let undone = self.doc_mut().undo();
+        self.clamp_focus();
+        undone
+    }
+
+    /// Keep the focused cell inside the matrix, after rows or cells are
+    /// removed, maybe from another view.
+    pub fn clamp_focus(&mut self) {
+        let doc = self.document.borrow();
+        let (row, col) = &mut self.cell_focused;
+        *row = (*row).min(doc.matrix.len().saturating_sub(1));
+        let len = doc.matrix.get(*row).map_or(0, |r| r.len());
+        *col = (*col).min(len.saturating_sub(1));
+    }
+
+    /// A line about the state of the table, to be shown by the page.
+    pub fn status(&self) -> Option<Line<'static>> {
+        let (row, col) = self.cell_focused;
+        let doc = self.doc();
+
+        if let Some(reason) = doc.read_only() {
+            return Some(Line::from(reason.yellow()));
+        }
+
+        if let Some(error) = &doc.save_error {
+            return Some(Line::from(vec![
+                format!("Could not save: {error}, try again: ").red(),
+                "W".bold().blue(),
+            ]));
+        }
+
+        if let Some(v) = doc.cell_violations(row, col).next() {
+            return Some(Line::from(v.message.clone().red()));
+        }
+
+        if doc.formulas.is_formula((row, col)) {
+            let raw = doc.get((row, col)).unwrap_or_default();
+            let mut line = vec![raw.to_string().italic()];
+            if let Some(error) = doc.formulas.error((row, col)) {
+                line.push(format!(" {error}").red());
+            }
+            line.push(match doc.save_values {
+                true => " saving computed values, save formulas: ".into(),
+                false => " saving formulas, save computed values: ".into(),
+            });
+            line.push("F".bold().blue());
+            return Some(Line::from(line));
+        }
+
+        if doc.violations.is_empty() {
+            return None;
+        }
+
+        let mut line =
+            vec![format!("{} schema violations", doc.violations.len()).red()];
+        if doc.dirty {
+            line.push(", changes not saved, save anyway: ".into());
+            line.push("W".bold().blue());
+        }
+        Some(Line::from(line))
+    }
+
+    fn draw_stats(&self, f: &mut Frame, rect: Rect, stats: &ColumnStats) {
+        let col = self.cell_focused.1;
+        let area = centered_rect(60, 60, rect);
+        f.render_widget(Clear, area);
+
+        let name = self.doc().column_names().get(col).cloned();
+        let name = name.unwrap_or_else(|| column_name(col));
+        let block = Block::default()
+            .title(format!("Statistics of {name}"))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let mut lines: Vec<Line> = vec![
+            Line::from(format!("count:    {}", stats.count)),
+            Line::from(format!("distinct: {}", stats.distinct)),
+            Line::from(format!("nulls:    {}", stats.nulls)),
+            Line::from(format!(
+                "min:      {}",
+                stats.min.as_deref().unwrap_or("-")
+            )),
+            Line::from(format!(
+                "max:      {}",
+                stats.max.as_deref().unwrap_or("-")
+            )),
+        ];
+        if let Some(n) = &stats.numeric {
+            lines.push(Line::from(format!("sum:      {}", n.sum)));
+            lines.push(Line::from(format!("mean:     {:.4}", n.mean)));
+            lines.push(Line::from(format!("median:   {}", n.median)));
+            lines.push(Line::from(format!("stddev:   {:.4}", n.stddev)));
+        }
+        if !stats.top.is_empty() {
+            lines.push(Line::from("most frequent:"));
+        }
+        for (value, n) in &stats.top {
+            lines.push(Line::from(format!("  {n:>6} {value}")));
+        }
+
+        let [summary, chart, _] = *Layout::new(
+            Direction::Vertical,
+            [
+                Constraint::Length(lines.len() as u16 + 1),
+                Constraint::Length(8),
+                Constraint::Min(0),
+            ],
+        )
+        .split(inner) else {
+            return;
+        };
+        f.render_widget(Paragraph::new(lines), summary);
+
+        // the distribution of numbers, or how often the top values appear
+        if stats.histogram.is_empty() {
+            let data: Vec<(&str, u64)> = stats
+                .top
+                .iter()
+                .map(|(v, n)| (v.as_str(), *n as u64))
+                .collect();
+            let bar_width = data
+                .iter()
+                .map(|(v, _)| v.chars().count())
+                .max()
+                .unwrap_or(1)
+                .min(12) as u16;
+            let bars = BarChart::default()
+                .data(&data)
+                .bar_width(bar_width)
+                .bar_gap(1)
+                .bar_style(Style::default().fg(Color::Cyan))
+                .value_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+            f.render_widget(bars, chart);
+        } else {
+            let sparkline = Sparkline::default()
+                .block(Block::default().title(format!(
+                    "Histogram from {} to {}",
+                    stats.min.as_deref().unwrap_or("-"),
+                    stats.max.as_deref().unwrap_or("-")
+                )))
+                .data(&stats.histogram)
+                .style(Style::default().fg(Color::Cyan));
+            f.render_widget(sparkline, chart);
         }
     }
 
-    pub fn get_focused_cell(&mut self) -> Option<&str> {
-        self.matrix
-            .get(self.cell_focused.0)
-            .and_then(|r| r.get(self.cell_focused.1).map(|s| s.as_str()))
+    /// Width of each column, enough to fit its widest value and its label,
+    /// unless it was resized.
+    fn column_widths(&self) -> Vec<u16> {
+        let doc = self.doc();
+        (0..doc.column_count())
+            .map(|col| {
+                if let Some(width) = self.widths.get(&col) {
+                    return *width;
+                }
+                doc.computed
+                    .iter()
+                    .filter_map(|r| r.get(col))
+                    .map(|v| v.chars().count())
+                    .chain([self.column_label(col).chars().count()])
+                    .max()
+                    .unwrap_or(0) as u16
+            })
+            .collect()
     }
 
-    pub fn get_mut_focused_cell(&mut self) -> Option<&mut String> {
-        self.matrix
-            .get_mut(self.cell_focused.0)
-            .and_then(|r| r.get_mut(self.cell_focused.1))
+    /// The label rendered above a column, its name and type.
+    fn column_label(&self, col: usize) -> String {
+        let t = self.doc().column_types.get(col).copied().unwrap_or_default();
+        format!("{} {}", column_name(col), t.name())
     }
 
-    pub fn get_mut_focused_cell_coordinates(
-        &mut self,
-    ) -> Option<(&mut usize, &mut usize)> {
-        self.matrix.get_mut(self.cell_focused.0).and_then(|r| {
-            r.get_mut(self.cell_focused.1).and_then(|_| {
-                Some((&mut self.cell_focused.0, &mut self.cell_focused.1))
+    /// The values of the selected cells, raw like the matrix, row by row.
+    pub fn selected(&self) -> Option<Vec<Vec<String>>> {
+        let ((r1, c1), (r2, c2)) = self.selection?;
+        let doc = self.doc();
+        let values = (r1.min(r2)..=r1.max(r2))
+            .map(|row| {
+                (c1.min(c2)..=c1.max(c2))
+                    .map(|col| doc.get((row, col)).unwrap_or_default().into())
+                    .collect()
             })
+            .collect();
+        Some(values)
+    }
+
+    fn is_in_selection(&self, (row, col): Cell) -> bool {
+        self.selection.is_some_and(|((r1, c1), (r2, c2))| {
+            (r1.min(r2)..=r1.max(r2)).contains(&row)
+                && (c1.min(c2)..=c1.max(c2)).contains(&col)
         })
     }
 
-    /// Delete the focused cell and return it's value, if the deletion happened
-    /// successfully
-    pub fn delete_focused_cell(&mut self) -> Result<String> {
-        if let Some(row) = self.matrix.get_mut(self.cell_focused.0) {
-            let r = Ok(row.remove(self.cell_focused.1));
-            self.sync_file()?;
-            return r;
+    /// The cell drawn at a position of the screen.
+    fn cell_at(&self, x: u16, y: u16) -> Option<Cell> {
+        // rows are drawn every other line, below the labels
+        let line = y.checked_sub(self.area.y + 2)?;
+        if y >= self.area.bottom() || line % 2 != 0 {
+            return None;
+        }
+        let row = self.offset + line as usize / 2;
+        let col =
+            self.columns.iter().position(|c| x >= c.x && x < c.right())?;
+        self.doc().get((row, col)).map(|_| (row, col))
+    }
+
+    /// The column whose right border is at a position of the labels line.
+    fn border_at(&self, x: u16, y: u16) -> Option<usize> {
+        if y != self.area.y {
+            return None;
         }
-        Err(eyre!("Could not delete cell"))
+        self.columns.iter().position(|c| c.width > 0 && x == c.right())
     }
 
-    /// Synchronize the struct and write all data to the file in the disk.
-    pub fn sync_file(&self) -> Result<()> {
-        let mut r = String::new();
-        for row in self.matrix.iter() {
-            let mut line: String =
-                row.iter().map(|s| format!("\"{s}\",")).collect();
+    /// Scroll by `rows`, moving the focused row along.
+    fn scroll(&mut self, rows: isize) {
+        let len = self.doc().matrix.len();
+        let shift =
+            |n: usize| n.saturating_add_signed(rows).min(len.saturating_sub(1));
+        self.offset = shift(self.offset);
+        self.cell_focused.0 = shift(self.cell_focused.0);
+    }
 
-            // remote "," from the last item
-            line.pop();
+    fn handle_mouse(&mut self, m: MouseEvent) {
+        match m.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(col) = self.border_at(m.column, m.row) {
+                    self.drag = Some(Drag::Resize(col));
+                    return;
+                }
+                let Some(cell) = self.cell_at(m.column, m.row) else {
+                    return;
+                };
+                self.cell_focused = cell;
+                self.selection = None;
+                self.drag = Some(Drag::Select(cell));
+
+                let double = self.last_click.is_some_and(|(at, last)| {
+                    last == cell && at.elapsed() < DOUBLE_CLICK
+                });
+                self.last_click = Some((Instant::now(), cell));
+                if double && self.editable() {
+                    self.last_click = None;
+                    self.open_edit_popup();
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => match self.drag {
+                Some(Drag::Resize(col)) => {
+                    let Some(rect) = self.columns.get(col) else {
+                        return;
+                    };
+                    let width = m.column.saturating_sub(rect.x).max(1);
+                    self.widths.insert(col, width);
+                }
+                Some(Drag::Select(start)) => {
+                    if let Some(cell) = self.cell_at(m.column, m.row) {
+                        self.cell_focused = cell;
+                        self.selection =
+                            Some((start, cell)).filter(|_| start != cell);
+                    }
+                }
+                None => {}
+            },
+            MouseEventKind::Up(MouseButton::Left) => self.drag = None,
+            MouseEventKind::ScrollDown => self.scroll(3),
+            MouseEventKind::ScrollUp => self.scroll(-3),
+            _ => {}
+        }
+    }
 
-            r.push_str(&line);
-            r.push_str("\n");
+    /// Open the popup to edit the focused cell.
+    fn open_edit_popup(&mut self) {
+        if let Some(value) = self.get_focused_cell() {
+            let values = self.doc().column_values(self.cell_focused.1);
+            let input = Input::new()
+                .value(value)
+                .completions(values)
+                .focused(true)
+                .mode(Mode::Insert);
+
+            self.edit_input = Some(input);
+            self.show_edit_popup = true;
         }
+    }
 
-        spawn(async move {
-            let mut file = tokio::fs::OpenOptions::new()
-                .write(true)
-                .read(false)
-                .create(true)
-                .open("testdata.csv")
-                .await
-                .unwrap();
+    pub fn get_focused_cell(&self) -> Option<String> {
+        self.doc().get(self.cell_focused).map(|v| v.to_string())
+    }
 
-            let _r = file.write_all(r.as_bytes()).await.unwrap();
-        });
+    /// Delete the focused cell and return it's value, if the deletion happened
+    /// successfully
+    pub fn delete_focused_cell(&mut self) -> color_eyre::eyre::Result<String> {
+        let r = self.doc_mut().delete_cell(self.cell_focused);
+        self.clamp_focus();
+        r
+    }
 
-        Ok(())
+    /// Handle a key press while the edit popup is open.
+    fn handle_edit_key(&mut self, k: KeyEvent) {
+        let (row, col) = self.cell_focused;
+        let (is_header, expected) = {
+            let doc = self.doc();
+            (doc.has_header && row == 0, doc.infer_column(col, Some(row)))
+        };
+
+        let Some(input) = &mut self.edit_input else {
+            return;
+        };
+        let can_close = input.mode == Mode::Normal && input.pending.is_none();
+
+        match k.code {
+            // warn once if the value does not fit the column type, a second
+            // Enter saves it anyway
+            KeyCode::Enter
+                if !is_header
+                    && input.warning.is_none()
+                    && formula::formula_of(&input.value).is_none()
+                    && !expected.accepts(&input.value) =>
+            {
+                input.warning = Some(format!(
+                    "Not a valid {}, Enter again to save anyway",
+                    expected.name()
+                ));
+            }
+            KeyCode::Enter => {
+                let value = input.value.clone();
+                self.doc_mut().set_cell(self.cell_focused, value);
+                self.show_edit_popup = false;
+                self.edit_input = None;
+            }
+            KeyCode::Char('q') | KeyCode::Esc if can_close => {
+                self.show_edit_popup = false;
+                self.edit_input = None;
+            }
+            _ => {
+                input.handle_action(Action::Key(k));
+            }
+        }
     }
 }
 
 impl<'a> Component for CsvTable<'a> {
     fn draw(&mut self, f: &mut Frame, rect: Rect) {
-        let rows = Layout::new(
-            Direction::Vertical,
-            vec![Constraint::Length(2); self.matrix.len()],
-        )
-        .split(rect);
+        // another view of the document may have removed rows
+        self.clamp_focus();
+        self.area = rect;
+
+        // horizontal position of each column, clipped to the area
+        let mut x = rect.x;
+        let columns: Vec<Rect> = self
+            .column_widths()
+            .into_iter()
+            .map(|w| {
+                let w = w.min(rect.right().saturating_sub(x));
+                let col = Rect { x, width: w, ..rect };
+                x = (x + w + 1).min(rect.right());
+                col
+            })
+            .collect();
+        self.columns = columns.clone();
 
-        let mut matrix_iter = self.matrix.iter().flatten();
+        let cell = |col: &Rect, y: u16| Rect { y, height: 1, ..*col };
 
-        for ((row_i, layout_row), cell_row) in
-            rows.iter().enumerate().zip(self.matrix.iter())
-        {
-            let areas: Vec<Constraint> = cell_row
-                .iter()
-                .map(|v| {
-                    let w = v.chars().count();
-                    Constraint::Length(w as u16 + 1)
-                })
-                .collect();
+        // labels with the name and type of each column
+        for (col_i, col) in columns.iter().enumerate() {
+            let label = Paragraph::new(self.column_label(col_i))
+                .style(Style::default().fg(Color::DarkGray));
+            f.render_widget(label, cell(col, rect.y));
+        }
 
-            let cols = Layout::new(Direction::Horizontal, areas)
-                .split(*layout_row)
-                .iter()
-                .copied()
-                .collect_vec();
+        let lines = (rect.y + 2..rect.bottom()).step_by(2);
+
+        // scroll so the focused row is visible
+        let visible = lines.len().max(1);
+        let row = self.cell_focused.0;
+        if row < self.offset {
+            self.offset = row;
+        } else if row >= self.offset + visible {
+            self.offset = row + 1 - visible;
+        }
+
+        let doc = self.document.borrow();
+        for ((row_i, row), y) in
+            doc.computed.iter().enumerate().skip(self.offset).zip(lines)
+        {
+            let is_header = doc.has_header && row_i == 0;
 
-            for ((col_i, rect), cell_text) in
-                cols.into_iter().enumerate().zip(&mut matrix_iter)
+            for ((col_i, cell_text), col) in
+                row.iter().enumerate().zip(&columns)
             {
-                let (x, y) = self.cell_focused;
+                let (x, y_) = self.cell_focused;
 
                 // if the cell of this loop is focused
-                let is_selected = x == row_i && y == col_i;
+                let is_selected = self.focused && x == row_i && y_ == col_i;
 
                 let mut text = Paragraph::new(cell_text.clone());
 
-                if is_selected {
+                let is_numeric =
+                    doc.column_types.get(col_i).is_some_and(|t| t.is_numeric());
+
+                if is_header {
+                    text = text.bold().underlined();
+                } else if is_numeric {
+                    text = text.alignment(Alignment::Right);
+                }
+
+                if doc.formulas.is_formula((row_i, col_i)) {
+                    text = text.italic();
+                }
+
+                let is_invalid =
+                    doc.cell_violations(row_i, col_i).next().is_some();
+
+                let highlight = self.highlights.get(&(row_i, col_i));
+                if let Some(color) = highlight {
+                    text = text.bg(*color);
+                }
+
+                if self.is_in_selection((row_i, col_i)) {
+                    text = text.bg(Color::DarkGray);
+                }
+
+                if is_invalid {
+                    text = text.bg(Color::LightRed);
+                }
+
+                if is_selected && (is_invalid || highlight.is_some()) {
+                    text = text.reversed();
+                } else if is_selected {
                     text = text.fg(Color::Red);
                 }
 
-                f.render_widget(text, rect);
+                f.render_widget(text, cell(col, y));
             }
         }
+        drop(doc);
 
         if !self.show_edit_popup {
             self.edit_input = None;
@@ -186,6 +585,10 @@ impl<'a> Component for CsvTable<'a> {
             );
         }
 
+        if let Some(stats) = &self.stats {
+            self.draw_stats(f, rect, stats);
+        }
+
         if let Some(input) = &mut self.edit_input {
             let block =
                 Block::default().title("Editing Cell").borders(Borders::ALL);
@@ -196,22 +599,37 @@ impl<'a> Component for CsvTable<'a> {
 
             f.render_widget(Clear, area);
             input.draw(f, area);
+
+            // the help line and the bordered input take 4 lines
+            let dropdown = Rect {
+                y: area.y + 4,
+                height: rect.bottom().saturating_sub(area.y + 4),
+                ..area
+            };
+            input.draw_suggestions(f, dropdown);
         }
     }
 
     fn handle_action(&mut self, action: Action) -> HandleActionResponse {
-        let mut response = HandleActionResponse::default();
+        // while the popup is open, every key goes to the input, the table and
+        // the page should not react to vim keys like `h` or `q`.
+        if self.edit_input.is_some() {
+            if let Action::Key(k) = action {
+                self.handle_edit_key(k);
+            }
+            return HandleActionResponse::Ignore;
+        }
 
-        if let Some(input) = &mut self.edit_input {
+        if self.stats.is_some() {
             if let Action::Key(k) = action {
-                if k.code == KeyCode::Enter {
-                    *self.get_mut_focused_cell().unwrap() = input.value.clone();
-                    let _ = self.sync_file();
-                    self.show_edit_popup = false;
-                } else {
-                    input.handle_action(action);
+                match k.code {
+                    KeyCode::Char('s') | KeyCode::Char('q') | KeyCode::Esc => {
+                        self.stats = None;
+                    }
+                    _ => {}
                 }
             }
+            return HandleActionResponse::Ignore;
         }
 
         if self.show_delete_popup {
@@ -221,93 +639,75 @@ impl<'a> Component for CsvTable<'a> {
                         self.show_delete_popup = false;
                         let _ = self.delete_focused_cell();
                     }
-                    KeyCode::Char('n') | KeyCode::Char('N') => {
+                    KeyCode::Char('n')
+                    | KeyCode::Char('N')
+                    | KeyCode::Char('q')
+                    | KeyCode::Esc => {
                         self.show_delete_popup = false;
                     }
                     _ => {}
                 }
             }
+            return HandleActionResponse::Ignore;
         }
 
         match action {
+            Action::Mouse(m) => {
+                self.handle_mouse(m);
+                return HandleActionResponse::Ignore;
+            }
+            Action::Key(k)
+                if k.code == KeyCode::Esc && self.selection.is_some() =>
+            {
+                self.selection = None;
+                return HandleActionResponse::Ignore;
+            }
             Action::Key(k) => match k.code {
-                KeyCode::Char('j') => {
-                    if self.matrix.get(self.cell_focused.0 + 1).is_some() {
-                        self.cell_focused.0 += 1;
-                    }
+                KeyCode::Char('j')
+                    if self.cell_focused.0 + 1 < self.doc().matrix.len() =>
+                {
+                    self.cell_focused.0 += 1;
                 }
                 KeyCode::Char('k') => {
-                    if self
-                        .matrix
-                        .get(self.cell_focused.0.overflowing_sub(1).0)
-                        .is_some()
-                    {
-                        self.cell_focused.0 -= 1;
-                    }
+                    self.cell_focused.0 = self.cell_focused.0.saturating_sub(1);
                 }
-                KeyCode::Char('h') => {
-                    // focus the cell on the left of the current one, if it
-                    // exists
-                    if let Some((_row, col)) =
-                        self.get_mut_focused_cell_coordinates()
-                    {
-                        *col = if *col > 0 { *col - 1 } else { 0 };
-                    }
+                // focus the cell on the left of the current one, if it
+                // exists
+                KeyCode::Char('h') if self.get_focused_cell().is_some() => {
+                    self.cell_focused.1 = self.cell_focused.1.saturating_sub(1);
                 }
                 KeyCode::Char('l') => {
                     // focus the cell on the right of the current one, if it
                     // exists
-                    let col = &mut self.cell_focused.1;
-                    if let Some(cols) = self.matrix.get_mut(self.cell_focused.0)
-                    {
-                        *col = (*col + 1).min(cols.len() - 1);
-                    }
-                }
-                // open a popup to edit the cell
-                KeyCode::Char('e') | KeyCode::Enter => {
-                    if self.edit_input.is_none() {
-                        let input = Input::new(self.tx.clone())
-                            .value(self.get_focused_cell().unwrap().to_owned())
-                            .focused(true)
-                            .mode(Mode::Insert);
-
-                        self.edit_input = Some(input);
-                        self.show_edit_popup = true;
+                    let len = self
+                        .doc()
+                        .matrix
+                        .get(self.cell_focused.0)
+                        .map(|cols| cols.len());
+                    if let Some(len) = len {
+                        let col = &mut self.cell_focused.1;
+                        *col = (*col + 1).min(len.saturating_sub(1));
                     }
                 }
-                KeyCode::Char('q') | KeyCode::Esc => {
-                    if self.show_delete_popup {
-                        self.show_delete_popup = false;
-                        response = HandleActionResponse::Ignore;
-                    }
-                    if let Some(input) = &mut self.edit_input {
-                        if input.mode == Mode::Normal {
-                            self.show_edit_popup = false;
-                            self.edit_input = None;
-                        }
-                        response = HandleActionResponse::Ignore;
-                    }
+                KeyCode::Char('s') => {
+                    let stats = self.doc().column_stats(self.cell_focused.1);
+                    self.stats = Some(stats);
                 }
+                _ if !self.editable() => {}
+                // open a popup to edit the cell
+                KeyCode::Char('e') | KeyCode::Enter => self.open_edit_popup(),
                 KeyCode::Char('d') => {
-                    if !self.show_edit_popup
-                        && self.edit_input.is_none()
-                        && !self.show_delete_popup
-                    {
-                        self.show_delete_popup = true;
-                    }
+                    self.show_delete_popup = true;
+                }
+                KeyCode::Char('u') => {
+                    self.undo();
                 }
+                KeyCode::Char('W') => self.doc_mut().save(),
+                KeyCode::Char('F') => self.doc_mut().toggle_save_values(),
                 _ => {}
             },
             _ => {}
         }
-        response
-    }
-
-    fn focus(&mut self) {
-        self.focused = true;
-    }
-
-    fn unfocus(&mut self) {
-        self.focused = false;
+        HandleActionResponse::default()
     }
 }
@@ -1,4 +1,4 @@
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyModifiers};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::*,
@@ -6,9 +6,8 @@ use ratatui::{
     widgets::*,
     Frame,
 };
-use tokio::sync::mpsc;
 
-use crate::action::Action;
+use crate::{action::Action, completion};
 
 use super::{Component, HandleActionResponse};
 
@@ -19,26 +18,85 @@ pub enum Mode {
     Insert,
 }
 
+/// A normal mode command that is waiting for more keys, like the `d` in `dw`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Pending {
+    Delete,
+    Change,
+    /// `ci`, waiting for the text object, only `w` is supported.
+    ChangeInner,
+    Replace,
+}
+
+/// The class of a char, used to find word boundaries the same way vim does.
+/// A word is either a sequence of keyword chars or a sequence of other non
+/// blank chars.
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Blank,
+    Keyword,
+    Punctuation,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        if c.is_whitespace() {
+            CharClass::Blank
+        } else if c.is_alphanumeric() || c == '_' {
+            CharClass::Keyword
+        } else {
+            CharClass::Punctuation
+        }
+    }
+}
+
 pub struct Input<'a> {
     pub focused: bool,
     pub block: Block<'a>,
     pub value: String,
     pub mode: Mode,
+    /// Position of the cursor, in chars, not bytes.
     pub cursor_position: usize,
-    tx: mpsc::UnboundedSender<Action>,
+    /// A normal mode command waiting for its motion or char.
+    pub pending: Option<Pending>,
+    /// Snapshots of (value, cursor_position) taken before each change, used
+    /// by `u`.
+    history: Vec<(String, usize)>,
+    /// Values the input can be completed to, with how often they appear.
+    completions: Vec<(String, usize)>,
+    /// The completions matching the current value, best first.
+    pub suggestions: Vec<String>,
+    /// Which suggestion is highlighted on the dropdown.
+    pub suggestion_selected: Option<usize>,
+    /// Rendered instead of the help message, until the next key press.
+    pub warning: Option<String>,
+}
+
+/// How many suggestions are shown on the dropdown.
+const MAX_SUGGESTIONS: usize = 5;
+
+impl Default for Input<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> Input<'a> {
-    pub fn new(tx: mpsc::UnboundedSender<Action>) -> Self {
+    pub fn new() -> Self {
         let block = Block::default().borders(Borders::ALL).title("Input");
 
         Self {
-            tx,
             block,
             value: "".into(),
             mode: Mode::default(),
             focused: false,
             cursor_position: 0,
+            pending: None,
+            history: Vec::new(),
+            completions: Vec::new(),
+            suggestions: Vec::new(),
+            suggestion_selected: None,
+            warning: None,
         }
     }
 
@@ -48,12 +106,11 @@ impl<'a> Input<'a> {
         self
     }
 
-    pub fn block(mut self, block: Block<'a>) -> Self {
-        self.block = block;
-        self
-    }
-
     pub fn mode(mut self, mode: Mode) -> Self {
+        // starting in insert mode counts as a change that `u` can undo
+        if mode == Mode::Insert {
+            self.snapshot();
+        }
         self.mode = mode;
         self
     }
@@ -62,6 +119,105 @@ impl<'a> Input<'a> {
         self.focused = focused;
         self
     }
+
+    pub fn completions(mut self, completions: Vec<(String, usize)>) -> Self {
+        self.completions = completions;
+        self.update_suggestions();
+        self
+    }
+}
+
+/// Single line rendering, used by the command line.
+impl<'a> Input<'a> {
+    /// Render the value on a single line after `prompt`, without borders or
+    /// help message.
+    pub fn draw_inline(&self, f: &mut Frame, rect: Rect, prompt: &str) {
+        let line = Line::from(vec![prompt.bold(), self.value.as_str().into()]);
+        f.render_widget(Paragraph::new(line), rect);
+
+        if self.focused {
+            let x = prompt.chars().count() + self.cursor_position;
+            f.set_cursor(rect.x + x as u16, rect.y);
+        }
+    }
+}
+
+/// Autocomplete.
+impl<'a> Input<'a> {
+    fn update_suggestions(&mut self) {
+        let suggestions =
+            completion::rank(&self.completions, &self.value, MAX_SUGGESTIONS);
+
+        if suggestions != self.suggestions {
+            self.suggestion_selected = None;
+        }
+        self.suggestions = suggestions;
+    }
+
+    fn select_next_suggestion(&mut self) {
+        if self.suggestions.is_empty() {
+            return;
+        }
+        self.suggestion_selected = Some(match self.suggestion_selected {
+            Some(i) => (i + 1) % self.suggestions.len(),
+            None => 0,
+        });
+    }
+
+    fn select_prev_suggestion(&mut self) {
+        if self.suggestions.is_empty() {
+            return;
+        }
+        self.suggestion_selected = Some(match self.suggestion_selected {
+            Some(0) | None => self.suggestions.len() - 1,
+            Some(i) => i - 1,
+        });
+    }
+
+    /// Replace the value with the highlighted suggestion, or the best one if
+    /// none is highlighted.
+    fn accept_suggestion(&mut self) {
+        let i = self.suggestion_selected.unwrap_or(0);
+        if let Some(suggestion) = self.suggestions.get(i).cloned() {
+            self.snapshot();
+            self.cursor_position = suggestion.chars().count();
+            self.value = suggestion;
+            self.cursor_position = self.clamp_cursor(self.cursor_position);
+        }
+        self.update_suggestions();
+    }
+
+    /// Render the suggestions as a dropdown, `rect` should be right under the
+    /// input.
+    pub fn draw_suggestions(&self, f: &mut Frame, rect: Rect) {
+        if self.suggestions.is_empty() {
+            return;
+        }
+
+        let items: Vec<ListItem> = self
+            .suggestions
+            .iter()
+            .map(|s| ListItem::new(s.as_str()))
+            .collect();
+
+        let height = (items.len() as u16 + 2).min(rect.height);
+        let area = Rect { height, ..rect };
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title("Tab to complete")
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded),
+            )
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+
+        let mut state =
+            ListState::default().with_selected(self.suggestion_selected);
+
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(list, area, &mut state);
+    }
 }
 
 impl<'a> Input<'a> {
@@ -89,8 +245,19 @@ impl<'a> Input<'a> {
             self.move_cursor_left();
         }
     }
+
+    fn len(&self) -> usize {
+        self.value.chars().count()
+    }
+
+    /// In insert mode the cursor can sit after the last char, in normal mode
+    /// it is always on top of a char, like in vim.
     fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
-        new_cursor_pos.clamp(0, self.value.len())
+        let max = match self.mode {
+            Mode::Insert => self.len(),
+            Mode::Normal => self.len().saturating_sub(1),
+        };
+        new_cursor_pos.min(max)
     }
 
     fn reset_cursor(&mut self) {
@@ -112,12 +279,245 @@ impl<'a> Input<'a> {
         self.cursor_position = self.clamp_cursor(cursor_moved_right);
     }
 
+    /// Byte index of the char at the cursor, `value.len()` if the cursor is
+    /// after the last char.
+    fn byte_index(&self) -> usize {
+        self.value
+            .char_indices()
+            .map(|(i, _)| i)
+            .nth(self.cursor_position)
+            .unwrap_or(self.value.len())
+    }
+
     fn enter_char(&mut self, new_char: char) {
-        self.value.insert(self.cursor_position, new_char);
+        let index = self.byte_index();
+        self.value.insert(index, new_char);
         self.move_cursor_right();
     }
 }
 
+/// Vim normal mode.
+impl<'a> Input<'a> {
+    /// Save the current state so that `u` can go back to it.
+    fn snapshot(&mut self) {
+        self.history.push((self.value.clone(), self.cursor_position));
+    }
+
+    fn undo(&mut self) {
+        if let Some((value, cursor)) = self.history.pop() {
+            self.value = value;
+            self.cursor_position = self.clamp_cursor(cursor);
+        }
+    }
+
+    fn enter_insert(&mut self, cursor: usize) {
+        self.mode = Mode::Insert;
+        self.cursor_position = self.clamp_cursor(cursor);
+    }
+
+    fn enter_normal(&mut self) {
+        self.mode = Mode::Normal;
+        // like vim, leaving insert mode moves the cursor back one char
+        self.cursor_position =
+            self.clamp_cursor(self.cursor_position.saturating_sub(1));
+    }
+
+    /// Remove the chars in `start..end` and place the cursor on `start`.
+    fn delete_range(&mut self, start: usize, end: usize) {
+        let chars: Vec<char> = self.value.chars().collect();
+        let end = end.min(chars.len());
+        if start >= end {
+            return;
+        }
+        self.value = chars[..start].iter().chain(&chars[end..]).collect();
+        self.cursor_position = self.clamp_cursor(start);
+    }
+
+    fn chars(&self) -> Vec<char> {
+        self.value.chars().collect()
+    }
+
+    /// `w`, the start of the next word, or the end of the line.
+    fn next_word_start(&self) -> usize {
+        let chars = self.chars();
+        let mut i = self.cursor_position;
+        if let Some(c) = chars.get(i) {
+            let class = CharClass::of(*c);
+            while i < chars.len() && CharClass::of(chars[i]) == class {
+                i += 1;
+            }
+        }
+        while i < chars.len() && CharClass::of(chars[i]) == CharClass::Blank {
+            i += 1;
+        }
+        i
+    }
+
+    /// `b`, the start of the current or previous word.
+    fn prev_word_start(&self) -> usize {
+        let chars = self.chars();
+        let mut i = self.cursor_position.min(chars.len());
+        while i > 0 && CharClass::of(chars[i - 1]) == CharClass::Blank {
+            i -= 1;
+        }
+        if i == 0 {
+            return 0;
+        }
+        let class = CharClass::of(chars[i - 1]);
+        while i > 0 && CharClass::of(chars[i - 1]) == class {
+            i -= 1;
+        }
+        i
+    }
+
+    /// `e`, the last char of the current or next word.
+    fn word_end(&self) -> usize {
+        let chars = self.chars();
+        if chars.is_empty() {
+            return 0;
+        }
+        let mut i = self.cursor_position + 1;
+        while i < chars.len() && CharClass::of(chars[i]) == CharClass::Blank {
+            i += 1;
+        }
+        if i >= chars.len() {
+            return chars.len() - 1;
+        }
+        let class = CharClass::of(chars[i]);
+        while i + 1 < chars.len() && CharClass::of(chars[i + 1]) == class {
+            i += 1;
+        }
+        i
+    }
+
+    /// `iw`, the range of the run of chars of the same class under the
+    /// cursor.
+    fn inner_word(&self) -> (usize, usize) {
+        let chars = self.chars();
+        let Some(c) = chars.get(self.cursor_position) else {
+            return (self.cursor_position, self.cursor_position);
+        };
+        let class = CharClass::of(*c);
+        let mut start = self.cursor_position;
+        let mut end = self.cursor_position;
+        while start > 0 && CharClass::of(chars[start - 1]) == class {
+            start -= 1;
+        }
+        while end < chars.len() && CharClass::of(chars[end]) == class {
+            end += 1;
+        }
+        (start, end)
+    }
+
+    /// `I`, the first non blank char.
+    fn first_non_blank(&self) -> usize {
+        self.value.chars().take_while(|c| c.is_whitespace()).count()
+    }
+
+    /// Finish a pending command with the given key.
+    fn handle_pending(&mut self, pending: Pending, code: KeyCode) {
+        match (pending, code) {
+            (Pending::Delete, KeyCode::Char('w')) => {
+                self.snapshot();
+                let end = self.next_word_start();
+                self.delete_range(self.cursor_position, end);
+            }
+            (Pending::Delete, KeyCode::Char('d')) => {
+                self.snapshot();
+                self.value.clear();
+                self.reset_cursor();
+            }
+            // like vim, `cw` on a word behaves like `ce`
+            (Pending::Change, KeyCode::Char('w')) => {
+                self.snapshot();
+                let start = self.cursor_position;
+                let on_blank = self
+                    .chars()
+                    .get(start)
+                    .is_none_or(|c| CharClass::of(*c) == CharClass::Blank);
+                let end = if on_blank {
+                    self.next_word_start()
+                } else {
+                    self.word_end() + 1
+                };
+                self.delete_range(start, end);
+                self.enter_insert(start);
+            }
+            (Pending::Change, KeyCode::Char('i')) => {
+                self.pending = Some(Pending::ChangeInner);
+            }
+            (Pending::ChangeInner, KeyCode::Char('w')) => {
+                self.snapshot();
+                let (start, end) = self.inner_word();
+                self.delete_range(start, end);
+                self.enter_insert(start);
+            }
+            (Pending::Replace, KeyCode::Char(c)) => {
+                let index = self.cursor_position;
+                if index < self.len() {
+                    self.snapshot();
+                    self.value = self
+                        .value
+                        .chars()
+                        .enumerate()
+                        .map(|(i, v)| if i == index { c } else { v })
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_normal(&mut self, code: KeyCode) {
+        if let Some(pending) = self.pending.take() {
+            self.handle_pending(pending, code);
+            return;
+        }
+
+        match code {
+            KeyCode::Char('i') => {
+                self.snapshot();
+                self.mode = Mode::Insert;
+            }
+            KeyCode::Char('a') => {
+                self.snapshot();
+                self.enter_insert(self.cursor_position + 1);
+            }
+            KeyCode::Char('A') => {
+                self.snapshot();
+                self.enter_insert(self.len());
+            }
+            KeyCode::Char('I') => {
+                self.snapshot();
+                self.enter_insert(self.first_non_blank());
+            }
+            KeyCode::Char('h') | KeyCode::Left => self.move_cursor_left(),
+            KeyCode::Char('l') | KeyCode::Right => self.move_cursor_right(),
+            KeyCode::Char('w') => {
+                self.cursor_position = self.clamp_cursor(self.next_word_start())
+            }
+            KeyCode::Char('b') => self.cursor_position = self.prev_word_start(),
+            KeyCode::Char('e') => self.cursor_position = self.word_end(),
+            KeyCode::Char('0') | KeyCode::Home => self.reset_cursor(),
+            KeyCode::Char('$') | KeyCode::End => {
+                self.cursor_position = self.clamp_cursor(self.len())
+            }
+            KeyCode::Char('x') if self.cursor_position < self.len() => {
+                self.snapshot();
+                self.delete_range(
+                    self.cursor_position,
+                    self.cursor_position + 1,
+                );
+            }
+            KeyCode::Char('d') => self.pending = Some(Pending::Delete),
+            KeyCode::Char('c') => self.pending = Some(Pending::Change),
+            KeyCode::Char('r') => self.pending = Some(Pending::Replace),
+            KeyCode::Char('u') => self.undo(),
+            _ => {}
+        }
+    }
+}
+
 impl<'a> Component for Input<'a> {
     fn draw(&mut self, f: &mut Frame, rect: Rect) {
         let chunks = Layout::default()
@@ -138,7 +538,9 @@ impl<'a> Component for Input<'a> {
                     "q".bold(),
                     " to exit, ".into(),
                     "i".bold(),
-                    " to start editing.".bold(),
+                    " to start editing, ".into(),
+                    "Enter".bold(),
+                    " to save".into(),
                 ],
                 Style::default().add_modifier(Modifier::RAPID_BLINK),
             ),
@@ -155,6 +557,13 @@ impl<'a> Component for Input<'a> {
         };
         let mut text = Text::from(Line::from(msg));
         text.patch_style(style);
+
+        if let Some(warning) = &self.warning {
+            text = Text::styled(
+                warning.as_str(),
+                Style::default().fg(Color::Yellow),
+            );
+        }
         let help_message = Paragraph::new(text);
 
         // render help message
@@ -169,40 +578,59 @@ impl<'a> Component for Input<'a> {
 
         // render input
         f.render_widget(input, chunks[1]);
+
+        if self.focused {
+            let x = chunks[1].x + 1 + self.cursor_position as u16;
+            f.set_cursor(x, chunks[1].y + 1);
+        }
     }
 
     fn handle_action(&mut self, action: Action) -> HandleActionResponse {
+        let mut response = HandleActionResponse::default();
+
+        if let Action::Key(_) = action {
+            self.warning = None;
+        }
+
         match self.mode {
-            Mode::Normal if let Action::Key(k) = action => match k.code {
-                KeyCode::Char('i') => self.mode = Mode::Insert,
-                _ => {}
-            },
+            Mode::Normal if let Action::Key(k) = action => {
+                self.handle_normal(k.code);
+                response = HandleActionResponse::Ignore;
+            }
             Mode::Insert if let Action::Key(k) = action => match k.code {
                 KeyCode::Esc => {
-                    self.mode = Mode::Normal;
-                    return HandleActionResponse::Ignore;
+                    self.enter_normal();
+                    response = HandleActionResponse::Ignore;
+                }
+                KeyCode::Char('n')
+                    if k.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    self.select_next_suggestion()
+                }
+                KeyCode::Char('p')
+                    if k.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    self.select_prev_suggestion()
                 }
-
                 KeyCode::Char(c) => {
                     self.enter_char(c);
-                    return HandleActionResponse::Ignore;
+                    response = HandleActionResponse::Ignore;
                 }
                 KeyCode::Backspace => self.delete_char(),
                 KeyCode::Left => self.move_cursor_left(),
                 KeyCode::Right => self.move_cursor_right(),
+                KeyCode::Down => self.select_next_suggestion(),
+                KeyCode::Up | KeyCode::BackTab => self.select_prev_suggestion(),
+                KeyCode::Tab => self.accept_suggestion(),
                 KeyCode::Enter => self.submit_message(),
                 _ => {}
             },
             _ => {}
         }
-        HandleActionResponse::default()
-    }
 
-    fn focus(&mut self) {
-        self.focused = true;
-    }
-
-    fn unfocus(&mut self) {
-        self.focused = false;
+        if let Action::Key(_) = action {
+            self.update_suggestions();
+        }
+        response
     }
 }
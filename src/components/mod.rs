@@ -1,7 +1,5 @@
-pub mod counter;
 pub mod csv_table;
 pub mod input;
-pub mod table;
 
 use ratatui::{layout::Rect, Frame};
 
@@ -21,6 +19,4 @@ pub trait Component {
         action: crate::action::Action,
     ) -> HandleActionResponse;
     fn draw(&mut self, f: &mut Frame, rect: Rect);
-    fn focus(&mut self);
-    fn unfocus(&mut self);
 }
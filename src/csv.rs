@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+
+use crate::{codec, utils};
+
+/// Parse CSV text into a matrix of rows and cells. Fields can be quoted with
+/// `"`, a quoted field can contain commas, new lines and `""` for a quote.
+pub fn parse(text: &str) -> Vec<Vec<String>> {
+    let mut matrix = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                matrix.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        matrix.push(row);
+    }
+
+    matrix
+}
+
+/// Write a matrix as CSV, every field is quoted.
+pub fn to_string(matrix: &[Vec<String>]) -> String {
+    let mut r = String::new();
+    for row in matrix {
+        let mut line: String = row
+            .iter()
+            .map(|s| format!("\"{}\",", s.replace('"', "\"\"")))
+            .collect();
+
+        // remove "," from the last item
+        line.pop();
+
+        r.push_str(&line);
+        r.push('\n');
+    }
+    r
+}
+
+/// Read and parse a CSV file.
+pub fn load(path: &Path) -> Result<Vec<Vec<String>>> {
+    Ok(parse(&codec::read_to_string(path)?))
+}
+
+/// Write a matrix to a CSV file, atomically.
+pub fn save(path: &Path, matrix: &[Vec<String>]) -> Result<()> {
+    utils::write_atomic(path, to_string(matrix))
+}
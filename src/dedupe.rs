@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+/// How values are compared to find duplicates.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Options {
+    pub ignore_case: bool,
+    /// Leading and trailing whitespace is left out, and runs of whitespace
+    /// are the same as a single space.
+    pub ignore_space: bool,
+}
+
+impl Options {
+    fn normalize(&self, value: &str) -> String {
+        let value = match self.ignore_space {
+            true => value.split_whitespace().collect::<Vec<_>>().join(" "),
+            false => value.to_string(),
+        };
+        match self.ignore_case {
+            true => value.to_lowercase(),
+            false => value,
+        }
+    }
+}
+
+/// Find the rows that are duplicates of each other, comparing the `keys`
+/// columns, or the whole row when there are no keys. Returns the groups of
+/// row indexes with more than one row, in the order they first appear.
+pub fn groups(
+    rows: &[Vec<String>],
+    keys: &[usize],
+    options: Options,
+) -> Vec<Vec<usize>> {
+    let mut seen: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+
+    for (i, row) in rows.iter().enumerate() {
+        let key: Vec<String> = match keys.is_empty() {
+            true => {
+                // trailing empty cells do not make a row different
+                let len = row
+                    .iter()
+                    .rposition(|v| !v.is_empty())
+                    .map_or(0, |p| p + 1);
+                row[..len].iter().map(|v| options.normalize(v)).collect()
+            }
+            false => keys
+                .iter()
+                .map(|k| options.normalize(row.get(*k).map_or("", |v| v)))
+                .collect(),
+        };
+        match seen.get(&key) {
+            Some(group) => groups[*group].push(i),
+            None => {
+                seen.insert(key, groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+
+    groups.retain(|g| g.len() > 1);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|r| r.iter().map(|v| v.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn whole_rows() {
+        let rows = matrix(&[
+            &["a", "1"],
+            &["b", "2"],
+            &["a", "1", ""],
+            &["b", "3"],
+            &["a", "1"],
+        ]);
+        assert_eq!(groups(&rows, &[], Options::default()), [vec![0, 2, 4]]);
+    }
+
+    #[test]
+    fn key_columns() {
+        let rows = matrix(&[&["a", "1"], &["b", "2"], &["a", "3"], &["b"]]);
+        assert_eq!(
+            groups(&rows, &[0], Options::default()),
+            [vec![0, 2], vec![1, 3]]
+        );
+        assert!(groups(&rows, &[1], Options::default()).is_empty());
+    }
+
+    #[test]
+    fn case_and_space() {
+        let rows = matrix(&[
+            &["New  York "],
+            &["new york"],
+            &[" New York"],
+            &["NEW YORK"],
+        ]);
+        let options =
+            |ignore_case, ignore_space| Options { ignore_case, ignore_space };
+
+        assert!(groups(&rows, &[0], options(false, false)).is_empty());
+        assert_eq!(groups(&rows, &[0], options(true, false)), [vec![1, 3]]);
+        assert_eq!(groups(&rows, &[0], options(false, true)), [vec![0, 2]]);
+        assert_eq!(
+            groups(&rows, &[0], options(true, true)),
+            [vec![0, 1, 2, 3]]
+        );
+    }
+}
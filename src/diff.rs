@@ -0,0 +1,153 @@
+use std::collections::{HashMap, VecDeque};
+
+/// A row of the left table lined up with a row of the right table. A row
+/// only on the left was removed, and a row only on the right was added.
+pub type Pair = (Option<usize>, Option<usize>);
+
+/// The columns whose values differ between two rows, a missing cell is the
+/// same as an empty one.
+pub fn changed_cells(left: &[String], right: &[String]) -> Vec<usize> {
+    (0..left.len().max(right.len()))
+        .filter(|i| {
+            left.get(*i).map_or("", |v| v.as_str())
+                != right.get(*i).map_or("", |v| v.as_str())
+        })
+        .collect()
+}
+
+/// Line up the rows of two tables. With a `key`, the column of the key on
+/// each table, rows are matched by the value of the key, otherwise by their
+/// position. The header, if there is one, is always lined up with the other
+/// header.
+pub fn align(
+    left: &[Vec<String>],
+    right: &[Vec<String>],
+    key: Option<(usize, usize)>,
+    has_header: bool,
+) -> Vec<Pair> {
+    let start = if has_header { 1 } else { 0 };
+
+    let Some((left_key, right_key)) = key else {
+        return (0..left.len().max(right.len()))
+            .map(|i| {
+                (
+                    Some(i).filter(|i| *i < left.len()),
+                    Some(i).filter(|i| *i < right.len()),
+                )
+            })
+            .collect();
+    };
+
+    let value = |row: &Vec<String>, col: usize| {
+        row.get(col).map_or(String::new(), |v| v.clone())
+    };
+
+    // rows of the right table by the value of their key, a key can repeat
+    let mut index: HashMap<String, VecDeque<usize>> = HashMap::new();
+    for (i, row) in right.iter().enumerate().skip(start) {
+        index.entry(value(row, right_key)).or_default().push_back(i);
+    }
+
+    let mut pairs: Vec<Pair> = Vec::new();
+    if has_header && (!left.is_empty() || !right.is_empty()) {
+        pairs.push((
+            Some(0).filter(|_| !left.is_empty()),
+            Some(0).filter(|_| !right.is_empty()),
+        ));
+    }
+
+    let mut matched = vec![false; right.len()];
+    for (i, row) in left.iter().enumerate().skip(start) {
+        let r =
+            index.get_mut(&value(row, left_key)).and_then(|r| r.pop_front());
+        if let Some(r) = r {
+            matched[r] = true;
+        }
+        pairs.push((Some(i), r));
+    }
+
+    // added rows go after the row that comes before them on the right table
+    for r in (start..right.len()).filter(|r| !matched[*r]) {
+        let at = pairs
+            .iter()
+            .rposition(|(_, right)| right.is_some_and(|right| right < r))
+            .map_or(pairs.len().min(start), |i| i + 1);
+        pairs.insert(at, (None, Some(r)));
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|r| r.iter().map(|v| v.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn changed_cells_treat_missing_as_empty() {
+        let left = matrix(&[&["a", "b", ""]]).remove(0);
+        let right = matrix(&[&["a", "c"]]).remove(0);
+        assert_eq!(changed_cells(&left, &right), [1]);
+        assert_eq!(changed_cells(&right, &matrix(&[&["a", "c", "d"]])[0]), [2]);
+    }
+
+    #[test]
+    fn by_position() {
+        let left = matrix(&[&["id"], &["1"], &["2"], &["3"]]);
+        let right = matrix(&[&["id"], &["1"]]);
+        assert_eq!(
+            align(&left, &right, None, true),
+            [
+                (Some(0), Some(0)),
+                (Some(1), Some(1)),
+                (Some(2), None),
+                (Some(3), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn by_key() {
+        let left =
+            matrix(&[&["id", "v"], &["1", "a"], &["2", "b"], &["3", "c"]]);
+        let right =
+            matrix(&[&["v", "id"], &["x", "3"], &["y", "4"], &["z", "1"]]);
+        assert_eq!(
+            align(&left, &right, Some((0, 1)), true),
+            [
+                (Some(0), Some(0)),
+                (Some(1), Some(3)),
+                (Some(2), None),
+                (Some(3), Some(1)),
+                (None, Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_keys_match_in_order() {
+        let left = matrix(&[&["k"], &["k"]]);
+        let right = matrix(&[&["k"], &["k"], &["k"]]);
+        assert_eq!(
+            align(&left, &right, Some((0, 0)), false),
+            [(Some(0), Some(0)), (Some(1), Some(1)), (None, Some(2))]
+        );
+    }
+
+    #[test]
+    fn added_rows_before_every_match_go_first() {
+        let left = matrix(&[&["id"], &["2"]]);
+        let right = matrix(&[&["id"], &["1"], &["2"]]);
+        assert_eq!(
+            align(&left, &right, Some((0, 0)), true),
+            [(Some(0), Some(0)), (None, Some(1)), (Some(1), Some(2))]
+        );
+        assert_eq!(
+            align(&[], &right, Some((0, 0)), true),
+            [(None, Some(0)), (None, Some(1)), (None, Some(2))]
+        );
+    }
+}
@@ -0,0 +1,743 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use color_eyre::eyre::{eyre, Result};
+use itertools::Itertools;
+
+use crate::{
+    columnar, csv,
+    file::{self, FileFormat},
+    fixed,
+    formula::{self, Cell, Expr, Formulas},
+    json, pivot,
+    schema::{Schema, Violation},
+    sql, sqlite,
+    stats::ColumnStats,
+    types::{self, ColumnType},
+    utils::{self, column_index, column_name},
+    xlsx,
+};
+
+/// A document shown by many views, edits on one view are seen by the others.
+pub type SharedDocument = Rc<RefCell<Document>>;
+
+/// The cells of a table and what is derived from them, the file it is saved
+/// to and the history of its changes. How the table is shown, the cursor and
+/// the popups, belong to the views.
+pub struct Document {
+    /// Matrix of rows and cells
+    pub matrix: Vec<Vec<String>>,
+    /// An id for each row of the matrix, kept when rows move, so formats
+    /// that keep more of a row than its values, like its rowid on a
+    /// database, find it again. Rows read from the file have their index on
+    /// the matrix as it was read, and rows added since new ids.
+    pub row_ids: Vec<usize>,
+    /// The id the next row added gets.
+    next_row_id: usize,
+    /// What is shown on each cell, the matrix with formulas replaced by
+    /// their result.
+    pub computed: Vec<Vec<String>>,
+    /// Formulas of the matrix, cells starting with `=`.
+    pub formulas: Formulas,
+    /// Save the computed values instead of the formulas.
+    pub save_values: bool,
+    /// If the first row of the matrix holds the names of the columns.
+    pub has_header: bool,
+    /// Type of each column, inferred from the values below the header.
+    pub column_types: Vec<ColumnType>,
+    /// Contract that the table is validated against on every change.
+    pub schema: Option<Schema>,
+    /// What the table breaks of the schema, the file is not saved while
+    /// this is not empty.
+    pub violations: Vec<Violation>,
+    /// If there are changes not written to the file.
+    pub dirty: bool,
+    /// Why the file could not be written the last time the table was saved.
+    pub save_error: Option<String>,
+    /// Snapshots of the matrix and its row ids taken before each change,
+    /// used by `u`.
+    history: Vec<(Vec<Vec<String>>, Vec<usize>)>,
+    /// File the table is saved to, `None` for tables that only live in
+    /// memory, like the result of a query.
+    pub path: Option<PathBuf>,
+    /// How the file is written.
+    pub format: FileFormat,
+}
+
+impl Document {
+    /// A document that is not saved anywhere until it is given a path.
+    pub fn new(matrix: Vec<Vec<String>>) -> Self {
+        let mut document = Self {
+            has_header: types::has_header(&matrix),
+            column_types: Vec::new(),
+            computed: Vec::new(),
+            formulas: Formulas::new(&matrix),
+            save_values: false,
+            schema: None,
+            violations: Vec::new(),
+            dirty: false,
+            save_error: None,
+            history: Vec::new(),
+            path: None,
+            format: FileFormat::Csv,
+            row_ids: (0..matrix.len()).collect(),
+            next_row_id: matrix.len(),
+            matrix,
+        };
+        document.compute();
+        document.infer_types();
+        document
+    }
+
+    /// Read a file, changes are saved back to it in the same format. A fixed
+    /// width file is split by the widths on the schema.
+    pub fn open(path: &Path, schema: Option<Schema>) -> Result<Self> {
+        let (matrix, format) = file::load(path, schema.as_ref())?;
+        Ok(Self::new(matrix)
+            .path(Some(path.to_path_buf()))
+            .format(format)
+            .schema(schema))
+    }
+
+    /// Read a sheet of a workbook, changes are saved back to it when it is
+    /// the only sheet, or a table of a database.
+    pub fn open_sheet(path: &Path, sheet: &str) -> Result<Self> {
+        let (matrix, format) = file::load_sheet(path, Some(sheet))?;
+        Ok(Self::new(matrix).path(Some(path.to_path_buf())).format(format))
+    }
+
+    pub fn path(mut self, path: Option<PathBuf>) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub fn format(mut self, format: FileFormat) -> Self {
+        if format.has_header() {
+            self.has_header = true;
+            self.infer_types();
+        }
+        self.format = format;
+        self.validate();
+        self
+    }
+
+    /// Say if the first row is a header, instead of guessing it.
+    pub fn header(mut self, has_header: bool) -> Self {
+        self.has_header = has_header;
+        self.infer_types();
+        self
+    }
+
+    pub fn schema(mut self, schema: Option<Schema>) -> Self {
+        if let Some(header) = schema.as_ref().and_then(|s| s.header) {
+            self.has_header = header;
+            self.infer_types();
+        }
+        self.schema = schema;
+        self.validate();
+        self
+    }
+
+    pub fn shared(self) -> SharedDocument {
+        Rc::new(RefCell::new(self))
+    }
+
+    /// Name of the file, to tell documents apart, with the sheet when the
+    /// workbook has others and with the table of a database.
+    pub fn name(&self) -> String {
+        let name = self
+            .path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map_or("[no name]".into(), |n| n.to_string_lossy().into_owned());
+        match &self.format {
+            FileFormat::Xlsx(sheet) if sheet.has_others() => {
+                format!("{name}:{}", sheet.name)
+            }
+            FileFormat::Sqlite(source) => format!("{name}:{}", source.table),
+            _ => name,
+        }
+    }
+
+    /// The computed rows after the header, if there is one.
+    pub fn body(&self) -> &[Vec<String>] {
+        let start = if self.has_header { 1 } else { 0 };
+        self.computed.get(start..).unwrap_or_default()
+    }
+
+    /// The raw value of a cell.
+    pub fn get(&self, (row, col): Cell) -> Option<&str> {
+        self.matrix.get(row).and_then(|r| r.get(col)).map(|v| v.as_str())
+    }
+
+    /// Evaluate the formulas that changed and update `computed`.
+    fn compute(&mut self) {
+        if self.formulas.is_empty() {
+            self.computed = self.matrix.clone();
+            return;
+        }
+        self.computed = self
+            .matrix
+            .iter()
+            .enumerate()
+            .map(|(r, row)| {
+                (0..row.len())
+                    .map(|c| match self.formulas.is_formula((r, c)) {
+                        true => self
+                            .formulas
+                            .value(&self.matrix, (r, c))
+                            .to_string(),
+                        false => row[c].clone(),
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.matrix.iter().map(|r| r.len()).max().unwrap_or(0)
+    }
+
+    /// Infer the type of a column, leaving out the value on the row `skip`.
+    pub fn infer_column(&self, col: usize, skip: Option<usize>) -> ColumnType {
+        let offset = if self.has_header { 1 } else { 0 };
+        types::infer(
+            self.body()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| Some(i + offset) != skip)
+                .filter_map(|(_, r)| r.get(col))
+                .map(|v| v.as_str()),
+        )
+    }
+
+    /// Infer the type of every column again, should be called after the
+    /// matrix changes.
+    pub fn infer_types(&mut self) {
+        self.column_types = (0..self.column_count())
+            .map(|col| self.infer_column(col, None))
+            .collect();
+    }
+
+    pub fn validate(&mut self) {
+        self.violations = match &self.schema {
+            Some(schema) => schema.validate(&self.computed, self.has_header),
+            None => Vec::new(),
+        };
+        // values of a fixed width file would be cut
+        if let FileFormat::Fixed(layout) = &self.format {
+            self.violations.extend(fixed::overflows(layout, &self.computed));
+        }
+    }
+
+    /// Should be called after the matrix changes, with the cell that changed,
+    /// or None if many cells changed. The file is only saved if the table is
+    /// valid.
+    pub fn changed(&mut self, cell: Option<Cell>) {
+        match cell {
+            Some(cell) => self.formulas.update(&self.matrix, cell),
+            None => self.formulas = Formulas::new(&self.matrix),
+        }
+        self.compute();
+        self.infer_types();
+        self.validate();
+        self.dirty = true;
+
+        if self.violations.is_empty() {
+            self.save();
+        }
+    }
+
+    /// Remember the matrix as it is, should be called before changing it.
+    pub fn snapshot(&mut self) {
+        self.history.push((self.matrix.clone(), self.row_ids.clone()));
+    }
+
+    /// Go back to the matrix before the last change. Returns false if there
+    /// is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some((matrix, row_ids)) = self.history.pop() else {
+            return false;
+        };
+        self.matrix = matrix;
+        self.row_ids = row_ids;
+        self.changed(None);
+        true
+    }
+
+    /// Change the value of a cell, as a single change.
+    pub fn set_cell(&mut self, (row, col): Cell, value: String) {
+        if self.get((row, col)).is_none() {
+            return;
+        }
+        self.snapshot();
+        self.matrix[row][col] = value;
+        self.changed(Some((row, col)));
+    }
+
+    /// Set the cells from `at` to the values, row by row, as a single
+    /// change. Values that fall outside the table are left out.
+    pub fn set_range(&mut self, (row, col): Cell, values: &[Vec<String>]) {
+        self.snapshot();
+        for (r, values) in values.iter().enumerate() {
+            for (c, value) in values.iter().enumerate() {
+                if let Some(cell) = self
+                    .matrix
+                    .get_mut(row + r)
+                    .and_then(|cells| cells.get_mut(col + c))
+                {
+                    *cell = value.clone();
+                }
+            }
+        }
+        self.changed(None);
+    }
+
+    /// Delete a cell and return its value, the cells on its right move to
+    /// the left.
+    pub fn delete_cell(&mut self, (row, col): Cell) -> Result<String> {
+        if self.get((row, col)).is_none() {
+            return Err(eyre!("Could not delete cell"));
+        }
+        self.snapshot();
+        let value = self.matrix[row].remove(col);
+        // the cells on the right moved, so every formula is parsed again
+        self.changed(None);
+        Ok(value)
+    }
+
+    /// Insert a row before `at`, as a single change.
+    pub fn insert_row(&mut self, at: usize, values: Vec<String>) {
+        self.snapshot();
+        let at = at.min(self.matrix.len());
+        self.matrix.insert(at, values);
+        let id = self.new_row_id();
+        self.row_ids.insert(at, id);
+        self.changed(None);
+    }
+
+    /// Remove rows of the matrix as a single change.
+    pub fn remove_rows(&mut self, rows: &[usize]) {
+        self.snapshot();
+        let mut i = 0;
+        self.matrix.retain(|_| {
+            i += 1;
+            !rows.contains(&(i - 1))
+        });
+        let mut i = 0;
+        self.row_ids.retain(|_| {
+            i += 1;
+            !rows.contains(&(i - 1))
+        });
+        self.changed(None);
+    }
+
+    /// Replace a row as a single change.
+    pub fn replace_row(&mut self, row: usize, values: Vec<String>) {
+        self.snapshot();
+        if let Some(r) = self.matrix.get_mut(row) {
+            *r = values;
+        }
+        self.changed(None);
+    }
+
+    /// Replace the whole matrix as a single change, each row keeps the id
+    /// of the row that was at its place.
+    pub fn replace_matrix(&mut self, matrix: Vec<Vec<String>>) {
+        let rows = (0..matrix.len())
+            .map(|r| (r < self.matrix.len()).then_some(r))
+            .collect();
+        self.replace_rows(matrix, rows);
+    }
+
+    /// Replace the whole matrix as a single change, with the row each row
+    /// was at before, to keep its id, or `None` for new rows. A row that
+    /// was copied keeps its id only on its first copy.
+    pub fn replace_rows(
+        &mut self,
+        matrix: Vec<Vec<String>>,
+        rows: Vec<Option<usize>>,
+    ) {
+        self.snapshot();
+        let mut kept = HashSet::new();
+        let mut row_ids = Vec::with_capacity(matrix.len());
+        for r in 0..matrix.len() {
+            let id = rows.get(r).copied().flatten();
+            let id = id.and_then(|r| self.row_ids.get(r).copied());
+            row_ids.push(match id.filter(|id| kept.insert(*id)) {
+                Some(id) => id,
+                None => self.new_row_id(),
+            });
+        }
+        self.row_ids = row_ids;
+        self.matrix = matrix;
+        self.changed(None);
+    }
+
+    fn new_row_id(&mut self) -> usize {
+        self.next_row_id += 1;
+        self.next_row_id - 1
+    }
+
+    /// Why the table can not be written over its file, it is only shown
+    /// then. A sheet of a workbook with other sheets would lose them, and
+    /// nested or binary values of Parquet and Arrow files are not read back.
+    pub fn read_only(&self) -> Option<String> {
+        match &self.format {
+            FileFormat::Xlsx(sheet) if sheet.has_others() => Some(
+                "Read only, saving would lose the other sheets, edit it on a \
+                 file of its own: :w other.xlsx"
+                    .into(),
+            ),
+            FileFormat::Parquet(layout) | FileFormat::Arrow(layout) => {
+                layout.nested_column().map(|name| {
+                    format!(
+                        "Read only, column {name} has nested or binary \
+                         values, they can not be written back"
+                    )
+                })
+            }
+            FileFormat::Sqlite(source) if source.rowid.is_none() => {
+                Some(format!(
+                    "Read only, table {} has no rowid to tell its rows apart, \
+                     save it elsewhere to edit it: :w other.db",
+                    source.table
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// The content of the file in its format. Refused for a sheet of a
+    /// workbook with other sheets, they would be lost.
+    pub fn contents(&self) -> Result<Vec<u8>> {
+        if let FileFormat::Xlsx(sheet) = &self.format {
+            if sheet.has_others() {
+                return Err(eyre!(
+                    "The workbook has sheets other than {}, they would be \
+                     lost, write it to a file of its own",
+                    sheet.name
+                ));
+            }
+        }
+        self.contents_as(&self.format)
+    }
+
+    /// The content of the file in another format. Sheets, fixed width files
+    /// and Parquet or Arrow files get the computed values, formulas are not
+    /// written on them, and tables of a database the SQL that creates them.
+    pub fn contents_as(&self, format: &FileFormat) -> Result<Vec<u8>> {
+        let matrix =
+            if self.save_values { &self.computed } else { &self.matrix };
+        let start = if self.has_header { 1 } else { 0 };
+        let rows = matrix.get(start..).unwrap_or_default();
+        let ids = self.row_ids.get(start..).unwrap_or_default();
+        let text = match format {
+            FileFormat::Csv => csv::to_string(matrix),
+            FileFormat::Json(shape) => {
+                json::to_string(&self.column_names(), rows, ids, shape, false)?
+            }
+            FileFormat::Ndjson(shape) => {
+                json::to_string(&self.column_names(), rows, ids, shape, true)?
+            }
+            FileFormat::Xlsx(sheet) => {
+                return xlsx::to_bytes(
+                    &sheet.name,
+                    &self.computed,
+                    &self.column_types,
+                    self.has_header,
+                )
+            }
+            FileFormat::Fixed(layout) => {
+                fixed::to_string(layout, &self.computed)
+            }
+            FileFormat::Parquet(layout) => {
+                return columnar::to_parquet(
+                    layout,
+                    &self.column_names(),
+                    &self.column_types,
+                    self.body(),
+                    ids,
+                )
+            }
+            FileFormat::Arrow(layout) => {
+                return columnar::to_arrow(
+                    layout,
+                    &self.column_names(),
+                    &self.column_types,
+                    self.body(),
+                    ids,
+                )
+            }
+            FileFormat::Sqlite(source) => sqlite::dump(
+                &source.table,
+                &self.column_names(),
+                &self.column_types,
+                rows,
+            ),
+        };
+        Ok(text.into_bytes())
+    }
+
+    /// Write the changes to the table of a database, only the rows that
+    /// changed.
+    pub fn write_table(&mut self, path: &Path) -> Result<()> {
+        let columns = self.column_names();
+        let FileFormat::Sqlite(source) = &mut self.format else {
+            return Err(eyre!("{} is not a table of a database", self.name()));
+        };
+        let matrix =
+            if self.save_values { &self.computed } else { &self.matrix };
+        let start = if self.has_header { 1 } else { 0 };
+        let rows = matrix.get(start..).unwrap_or_default();
+        let ids = self.row_ids.get(start..).unwrap_or_default();
+        sqlite::write(path, source, &columns, &self.column_types, rows, ids)
+    }
+
+    /// Write the table to another file, in the format of its extension, and
+    /// save it there from now on. A table of a database replaces the table
+    /// of the same name.
+    pub fn save_as(&mut self, path: &Path) -> Result<()> {
+        let format = self.format.to(path);
+        match format {
+            FileFormat::Sqlite(_) => {
+                let previous = std::mem::replace(&mut self.format, format);
+                if let Err(e) = self.write_table(path) {
+                    self.format = previous;
+                    return Err(e);
+                }
+            }
+            format => {
+                utils::write_atomic(path, self.contents_as(&format)?)?;
+                self.format = format;
+            }
+        }
+        self.path = Some(path.to_path_buf());
+        self.dirty = false;
+        self.save_error = None;
+        Ok(())
+    }
+
+    /// Write the file, even if the table is not valid, keeping why it could
+    /// not be written for the status line.
+    pub fn save(&mut self) {
+        if self.path.is_some() {
+            self.save_error = self.write().err().map(|e| e.to_string());
+        }
+    }
+
+    /// Write the file, even if the table is not valid, or tell why it could
+    /// not be written.
+    pub fn write(&mut self) -> Result<()> {
+        self.sync_file()?;
+        self.dirty = false;
+        self.save_error = None;
+        Ok(())
+    }
+
+    /// Switch between saving the computed values and the formulas.
+    pub fn toggle_save_values(&mut self) {
+        self.save_values = !self.save_values;
+        self.dirty = true;
+        if self.violations.is_empty() {
+            self.save();
+        }
+    }
+
+    /// Violations of the schema on a cell.
+    pub fn cell_violations(
+        &self,
+        row: usize,
+        col: usize,
+    ) -> impl Iterator<Item = &Violation> {
+        self.violations.iter().filter(move |v| v.cell == Some((row, col)))
+    }
+
+    /// Compute the statistics of a column, leaving out the header.
+    pub fn column_stats(&self, col: usize) -> ColumnStats {
+        let values: Vec<&str> = self
+            .body()
+            .iter()
+            .map(|r| r.get(col).map_or("", |v| v.as_str()))
+            .collect();
+        let t = self.column_types.get(col).copied().unwrap_or_default();
+        ColumnStats::new(&values, t, 5, 40)
+    }
+
+    /// Find a column by the name on the header, or by its spreadsheet style
+    /// name, like `B`.
+    pub fn column_by_name(&self, name: &str) -> Option<usize> {
+        let header = self.matrix.first().filter(|_| self.has_header);
+        header
+            .and_then(|h| {
+                h.iter().position(|n| n == name).or_else(|| {
+                    h.iter().position(|n| n.eq_ignore_ascii_case(name))
+                })
+            })
+            .or_else(|| column_index(name).filter(|c| *c < self.column_count()))
+    }
+
+    /// Append a column computed row by row from `expr`, where names refer to
+    /// the values of other columns on the same row. A `live` column is
+    /// written as formulas, so it is computed again when the row changes,
+    /// otherwise the values are computed only once.
+    pub fn add_column(
+        &mut self,
+        name: &str,
+        expr: &str,
+        live: bool,
+    ) -> Result<()> {
+        let expr = self.parse_row_expr(expr)?;
+        let col = self.column_count();
+        let start = if self.has_header { 1 } else { 0 };
+
+        let values: Vec<String> = (start..self.matrix.len())
+            .map(|row| {
+                let expr = self.row_expr(&expr, row);
+                match live {
+                    true => format!("={expr}"),
+                    false => {
+                        self.formulas.eval(&self.matrix, &expr).to_string()
+                    }
+                }
+            })
+            .collect();
+
+        self.snapshot();
+        if self.has_header {
+            let header = &mut self.matrix[0];
+            header.resize(col, String::new());
+            header.push(name.to_string());
+        }
+        for (row, value) in self.matrix[start..].iter_mut().zip(values) {
+            row.resize(col, String::new());
+            row.push(value);
+        }
+
+        self.changed(None);
+        Ok(())
+    }
+
+    /// Parse an expression where names refer to columns, failing on names
+    /// that are not columns.
+    fn parse_row_expr(&self, src: &str) -> Result<Expr> {
+        let expr = formula::parse(src).map_err(|e| eyre!(e))?;
+        if let Some(unknown) =
+            expr.names().into_iter().find(|n| self.column_by_name(n).is_none())
+        {
+            return Err(eyre!("Unknown column {unknown}"));
+        }
+        Ok(expr)
+    }
+
+    /// The expression with the names replaced by the cells of a row.
+    fn row_expr(&self, expr: &Expr, row: usize) -> Expr {
+        expr.clone().replace_names(&|name| {
+            self.column_by_name(name).map(|col| Expr::Ref((row, col)))
+        })
+    }
+
+    /// The rows after the header where `expr` is true, names refer to the
+    /// values of the columns on the same row, like `qty > 0`.
+    pub fn filter(&self, expr: &str) -> Result<Vec<usize>> {
+        let expr = self.parse_row_expr(expr)?;
+        let start = if self.has_header { 1 } else { 0 };
+        (start..self.matrix.len())
+            .filter_map(|row| {
+                let value = self
+                    .formulas
+                    .eval(&self.matrix, &self.row_expr(&expr, row));
+                match value.truthy() {
+                    Ok(true) => Some(Ok(row)),
+                    Ok(false) => None,
+                    Err(e) => Some(Err(eyre!("{e} on row {}", row + 1))),
+                }
+            })
+            .collect()
+    }
+
+    /// Sort the rows after the header by the computed values of a column,
+    /// as numbers when they are, as a single change.
+    pub fn sort(&mut self, col: usize, descending: bool) {
+        let start = if self.has_header { 1 } else { 0 };
+        let mut rows: Vec<usize> = (start..self.matrix.len()).collect();
+        let value = |row: &usize| {
+            self.computed[*row].get(col).map_or("", |v| v.as_str())
+        };
+        rows.sort_by(|a, b| {
+            let order = pivot::compare(value(a), value(b));
+            if descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        let mut order: Vec<usize> = (0..start.min(self.matrix.len())).collect();
+        order.extend(rows);
+        let matrix =
+            order.iter().map(|row| self.matrix[*row].clone()).collect();
+        self.replace_rows(matrix, order.into_iter().map(Some).collect());
+    }
+
+    /// Run an SQL query over the computed values, the table is named `this`
+    /// and its columns are named by the header. A table of a database is
+    /// queried next to the other tables of the database. The first row of
+    /// the result holds the names of the columns.
+    pub fn query(&self, sql: &str) -> Result<Vec<Vec<String>>> {
+        match (&self.format, &self.path) {
+            (FileFormat::Sqlite(source), Some(path)) => sqlite::query(
+                path,
+                &source.table,
+                &self.column_names(),
+                &self.column_types,
+                self.body(),
+                sql,
+            ),
+            _ => sql::query(
+                &self.column_names(),
+                &self.column_types,
+                self.body(),
+                sql,
+            ),
+        }
+    }
+
+    /// The names of the columns on the header, or their letters when there
+    /// is no header.
+    pub fn column_names(&self) -> Vec<String> {
+        match self.matrix.first() {
+            Some(header) if self.has_header => (0..self.column_count())
+                .map(|col| header.get(col).cloned().unwrap_or_default())
+                .collect(),
+            _ => (0..self.column_count()).map(column_name).collect(),
+        }
+    }
+
+    /// Distinct non empty values of a column, with how many times each one
+    /// appears.
+    pub fn column_values(&self, col: usize) -> Vec<(String, usize)> {
+        self.body()
+            .iter()
+            .filter_map(|row| row.get(col))
+            .filter(|v| !v.is_empty())
+            .counts()
+            .into_iter()
+            .map(|(v, count)| (v.to_owned(), count))
+            .collect()
+    }
+
+    /// Write the file, next to it first and then renamed over it, so it is
+    /// never left half written.
+    pub fn sync_file(&mut self) -> Result<()> {
+        let path = self.path.clone().ok_or(eyre!("No file to save to"))?;
+        if let FileFormat::Sqlite(_) = self.format {
+            return self.write_table(&path);
+        }
+        utils::write_atomic(&path, self.contents()?)
+    }
+}
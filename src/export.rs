@@ -0,0 +1,286 @@
+use std::{fmt::Write, path::Path, str::FromStr};
+
+use color_eyre::eyre::{eyre, Report, Result};
+use serde_json::{Number, Value};
+
+use crate::{
+    codec, csv,
+    document::Document,
+    file::FileFormat,
+    formula::Cell,
+    sql,
+    sqlite::{self, Source},
+    types::ColumnType,
+    utils,
+};
+
+/// A format the table can be exported to.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    #[default]
+    Csv,
+    /// An array of objects, with the header names as keys.
+    Json,
+    /// An object per line.
+    Ndjson,
+    /// A GitHub flavored Markdown table.
+    Markdown,
+    /// An HTML `<table>`.
+    Html,
+    /// A table on a SQLite database, replacing a table of the same name.
+    /// Written as the SQL that creates it when it is not written to a file.
+    Sqlite,
+}
+
+impl Format {
+    /// The format of a file by its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let path = codec::strip(path);
+        let extension = path.extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            "ndjson" | "jsonl" => Some(Format::Ndjson),
+            "md" | "markdown" => Some(Format::Markdown),
+            "html" | "htm" => Some(Format::Html),
+            "db" | "sqlite" | "sqlite3" => Some(Format::Sqlite),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "ndjson" | "jsonl" => Ok(Format::Ndjson),
+            "md" | "markdown" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            "sqlite" => Ok(Format::Sqlite),
+            _ => Err(eyre!(
+                "Unknown format {s}, use csv, json, ndjson, markdown, html or \
+                 sqlite"
+            )),
+        }
+    }
+}
+
+/// What is exported, the computed values of the rows below the header and
+/// the names and types of their columns.
+#[derive(Clone, Debug, Default)]
+pub struct Table {
+    /// The name of the table on a database, the name of the file or of the
+    /// table it was read from.
+    pub name: String,
+    /// If the table has a header, the columns are named by their letters
+    /// otherwise.
+    pub header: bool,
+    pub columns: Vec<String>,
+    pub types: Vec<ColumnType>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// The whole document, or only the cells between the corners of a
+    /// `selection`. The header is never part of the rows.
+    pub fn of(doc: &Document, selection: Option<(Cell, Cell)>) -> Self {
+        let start = if doc.has_header { 1 } else { 0 };
+        let (rows, cols) = match selection {
+            Some(((r1, c1), (r2, c2))) => (
+                r1.min(r2).max(start)..r1.max(r2) + 1,
+                c1.min(c2)..c1.max(c2) + 1,
+            ),
+            None => (start..doc.matrix.len(), 0..doc.column_count()),
+        };
+
+        let names = sql::column_names(&doc.column_names());
+        let name = match &doc.format {
+            FileFormat::Sqlite(source) => source.table.clone(),
+            _ => doc
+                .path
+                .as_ref()
+                .and_then(|p| p.file_stem())
+                .map_or("table".into(), |s| s.to_string_lossy().into_owned()),
+        };
+        Self {
+            name,
+            header: doc.has_header,
+            columns: cols.clone().map(|c| names[c].clone()).collect(),
+            types: cols
+                .clone()
+                .map(|c| doc.column_types.get(c).copied().unwrap_or_default())
+                .collect(),
+            rows: rows
+                .filter_map(|r| doc.computed.get(r))
+                .map(|row| {
+                    cols.clone()
+                        .map(|c| row.get(c).cloned().unwrap_or_default())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Write the table in a format, with a header when the format has one.
+pub fn render(table: &Table, format: Format) -> String {
+    match format {
+        Format::Csv => {
+            let mut matrix = match table.header {
+                true => vec![table.columns.clone()],
+                false => Vec::new(),
+            };
+            matrix.extend(table.rows.iter().cloned());
+            csv::to_string(&matrix)
+        }
+        Format::Json => {
+            let objects: Vec<String> =
+                table.rows.iter().map(|row| object(table, row)).collect();
+            match objects.is_empty() {
+                true => "[]\n".to_string(),
+                false => format!("[\n  {}\n]\n", objects.join(",\n  ")),
+            }
+        }
+        Format::Ndjson => {
+            table.rows.iter().map(|row| object(table, row) + "\n").collect()
+        }
+        Format::Markdown => markdown(table),
+        Format::Html => html(table),
+        Format::Sqlite => {
+            sqlite::dump(&table.name, &table.columns, &table.types, &table.rows)
+        }
+    }
+}
+
+/// Write the table to a file, in the format of its extension or CSV.
+pub fn save(path: &Path, table: &Table, format: Option<Format>) -> Result<()> {
+    let format = format.or(Format::from_path(path)).unwrap_or_default();
+    match format {
+        Format::Sqlite => sqlite::write(
+            path,
+            &mut Source::new(&table.name),
+            &table.columns,
+            &table.types,
+            &table.rows,
+            &(0..table.rows.len()).collect::<Vec<_>>(),
+        ),
+        format => utils::write_atomic(path, render(table, format)),
+    }
+}
+
+/// A row as a JSON object on a single line, keeping the order of the columns.
+fn object(table: &Table, row: &[String]) -> String {
+    let fields: Vec<String> = table
+        .columns
+        .iter()
+        .zip(&table.types)
+        .zip(row)
+        .map(|((name, t), value)| {
+            format!("{}: {}", Value::from(name.as_str()), json_value(value, *t))
+        })
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+/// A value as a JSON number or boolean when its column is one, null when it
+/// is empty and a string otherwise.
+fn json_value(value: &str, t: ColumnType) -> Value {
+    let trimmed = value.trim();
+    let typed = match t {
+        _ if trimmed.is_empty() => Some(Value::Null),
+        ColumnType::Integer => trimmed.parse::<i64>().ok().map(Value::from),
+        ColumnType::Float => trimmed
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        ColumnType::Boolean => match trimmed.to_ascii_lowercase().as_str() {
+            "true" | "yes" => Some(Value::Bool(true)),
+            "false" | "no" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    };
+    typed.unwrap_or_else(|| Value::from(value))
+}
+
+fn markdown(table: &Table) -> String {
+    let escape = |v: &str| v.replace('|', "\\|").replace('\n', "<br>");
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    let mut r = line(table.columns.iter().map(|c| escape(c)).collect());
+    r.push_str(&line(
+        table
+            .types
+            .iter()
+            .map(|t| if t.is_numeric() { "---:" } else { "---" }.to_string())
+            .collect(),
+    ));
+    for row in &table.rows {
+        r.push_str(&line(row.iter().map(|v| escape(v)).collect()));
+    }
+    r
+}
+
+fn html(table: &Table) -> String {
+    let escape = |v: &str| {
+        v.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+
+    let mut r = String::from("<table>\n  <thead>\n    <tr>");
+    for column in &table.columns {
+        let _ = write!(r, "<th>{}</th>", escape(column));
+    }
+    r.push_str("</tr>\n  </thead>\n  <tbody>\n");
+    for row in &table.rows {
+        r.push_str("    <tr>");
+        for value in row {
+            let _ = write!(r, "<td>{}</td>", escape(value));
+        }
+        r.push_str("</tr>\n");
+    }
+    r.push_str("  </tbody>\n</table>\n");
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(rows: &[&[&str]]) -> Table {
+        let matrix = rows
+            .iter()
+            .map(|r| r.iter().map(|v| v.to_string()).collect())
+            .collect();
+        Table::of(&Document::new(matrix).header(true), None)
+    }
+
+    #[test]
+    fn json_keeps_zero_padded_strings() {
+        let table = table(&[
+            &["zip", "qty", "ok"],
+            &["007", "1", "yes"],
+            &["02134", "20", ""],
+        ]);
+        assert_eq!(
+            render(&table, Format::Ndjson),
+            "{\"zip\": \"007\", \"qty\": 1, \"ok\": true}\n\
+             {\"zip\": \"02134\", \"qty\": 20, \"ok\": null}\n"
+        );
+    }
+
+    #[test]
+    fn markdown_escapes_pipes() {
+        let table = table(&[&["a", "b"], &["x|y", "1"]]);
+        assert_eq!(
+            render(&table, Format::Markdown),
+            "| a | b |\n| --- | ---: |\n| x\\|y | 1 |\n"
+        );
+    }
+}
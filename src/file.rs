@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    codec::{self, Codec},
+    columnar, csv, fixed, json,
+    schema::Schema,
+    sqlite, xlsx,
+};
+
+/// How a file is read and written, picked by its extension.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FileFormat {
+    #[default]
+    Csv,
+    /// An array of objects, flattened into columns.
+    Json(json::Shape),
+    /// An object per line, flattened into columns.
+    Ndjson(json::Shape),
+    /// A sheet of an Excel workbook.
+    Xlsx(xlsx::Sheet),
+    /// A table of a SQLite database, changes are written to it row by row.
+    Sqlite(sqlite::Source),
+    /// A Parquet file, written back with the column types it had.
+    Parquet(columnar::Layout),
+    /// An Arrow IPC file, written back with the column types it had.
+    Arrow(columnar::Layout),
+    /// Columns of a fixed width on each line, written back padded to it.
+    Fixed(fixed::Layout),
+}
+
+impl FileFormat {
+    /// The format of a file by its extension, CSV when it is not known. The
+    /// extension of a compression is left out, `data.json.gz` is JSON.
+    pub fn of(path: &Path) -> Self {
+        let path = &codec::strip(path);
+        let extension = path.extension().and_then(|e| e.to_str());
+        match extension.map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("json") => FileFormat::Json(json::Shape::default()),
+            Some("ndjson" | "jsonl") => {
+                FileFormat::Ndjson(json::Shape::default())
+            }
+            Some("xlsx") => FileFormat::Xlsx(xlsx::Sheet::default()),
+            Some("db" | "sqlite" | "sqlite3") => {
+                let stem = path.file_stem().unwrap_or_default();
+                FileFormat::Sqlite(sqlite::Source::new(&stem.to_string_lossy()))
+            }
+            Some("parquet") => FileFormat::Parquet(columnar::Layout::default()),
+            Some("arrow" | "arrows" | "feather" | "ipc") => {
+                FileFormat::Arrow(columnar::Layout::default())
+            }
+            Some("fwf" | "dat" | "prn") => {
+                FileFormat::Fixed(fixed::Layout::default())
+            }
+            _ => FileFormat::Csv,
+        }
+    }
+
+    /// The format to write a file with by its extension, keeping the shape
+    /// of JSON when this one is JSON too, the name of the sheet or table
+    /// when this one is a sheet or table too, the column types between
+    /// Parquet and Arrow files, and the layout of a fixed width file.
+    pub fn to(&self, path: &Path) -> Self {
+        match (Self::of(path), self) {
+            (FileFormat::Xlsx(_), FileFormat::Xlsx(sheet)) => {
+                FileFormat::Xlsx(xlsx::Sheet::single(&sheet.name))
+            }
+            (FileFormat::Sqlite(_), FileFormat::Sqlite(source)) => {
+                FileFormat::Sqlite(sqlite::Source::new(&source.table))
+            }
+            (FileFormat::Fixed(_), FileFormat::Fixed(layout)) => {
+                FileFormat::Fixed(layout.clone())
+            }
+            (FileFormat::Parquet(_), FileFormat::Parquet(layout)) => {
+                FileFormat::Parquet(layout.clone())
+            }
+            (FileFormat::Parquet(_), FileFormat::Arrow(layout)) => {
+                FileFormat::Parquet(layout.schema_only())
+            }
+            (
+                FileFormat::Arrow(_),
+                FileFormat::Parquet(layout) | FileFormat::Arrow(layout),
+            ) => FileFormat::Arrow(layout.schema_only()),
+            (FileFormat::Json(_), _) if let Some(shape) = self.shape() => {
+                FileFormat::Json(shape)
+            }
+            (FileFormat::Ndjson(_), _) if let Some(shape) = self.shape() => {
+                FileFormat::Ndjson(shape)
+            }
+            (format, _) => format,
+        }
+    }
+
+    /// How the values of a JSON file looked.
+    pub fn shape(&self) -> Option<json::Shape> {
+        match self {
+            FileFormat::Json(shape) | FileFormat::Ndjson(shape) => {
+                Some(shape.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// The sheet or table the table was read from, and every sheet or table
+    /// of its file.
+    pub fn sheets(&self) -> Option<(&str, &[String])> {
+        match self {
+            FileFormat::Xlsx(sheet) => Some((&sheet.name, &sheet.workbook)),
+            FileFormat::Sqlite(source) => Some((&source.table, &source.tables)),
+            _ => None,
+        }
+    }
+
+    /// If the first row always holds the names of the columns.
+    pub fn has_header(&self) -> bool {
+        self.shape().is_some()
+            || matches!(
+                self,
+                FileFormat::Sqlite(_)
+                    | FileFormat::Parquet(_)
+                    | FileFormat::Arrow(_)
+            )
+    }
+}
+
+/// Read a file in the format of its extension, the first sheet of a
+/// workbook or the first table of a database. A fixed width file is split by
+/// the widths of the columns on the schema, or where its columns line up.
+pub fn load(
+    path: &Path,
+    schema: Option<&Schema>,
+) -> Result<(Vec<Vec<String>>, FileFormat)> {
+    let format = FileFormat::of(path);
+    match format {
+        // only text is decompressed as it is read
+        FileFormat::Xlsx(_)
+        | FileFormat::Sqlite(_)
+        | FileFormat::Parquet(_)
+        | FileFormat::Arrow(_)
+            if Codec::detect(path).is_some() =>
+        {
+            return Err(eyre!("{} can not be read compressed", path.display()))
+        }
+        FileFormat::Csv => return Ok((csv::load(path)?, format)),
+        FileFormat::Xlsx(_) | FileFormat::Sqlite(_) => {
+            return load_sheet(path, None)
+        }
+        FileFormat::Fixed(_) => {
+            let widths = schema.and_then(|s| s.widths());
+            let (matrix, layout) = fixed::load(path, widths.as_deref())?;
+            return Ok((matrix, FileFormat::Fixed(layout)));
+        }
+        FileFormat::Parquet(_) => {
+            let (matrix, layout) = columnar::load_parquet(path)?;
+            return Ok((matrix, FileFormat::Parquet(layout)));
+        }
+        FileFormat::Arrow(_) => {
+            let (matrix, layout) = columnar::load_arrow(path)?;
+            return Ok((matrix, FileFormat::Arrow(layout)));
+        }
+        _ => {}
+    }
+
+    let text = codec::read_to_string(path)?;
+    let ndjson = matches!(format, FileFormat::Ndjson(_));
+    let (matrix, shape) = json::parse(&text, ndjson)
+        .wrap_err_with(|| format!("Could not read {}", path.display()))?;
+    let format = match ndjson {
+        true => FileFormat::Ndjson(shape),
+        false => FileFormat::Json(shape),
+    };
+    Ok((matrix, format))
+}
+
+/// Read a sheet of a workbook or a table of a database, or the first one.
+pub fn load_sheet(
+    path: &Path,
+    name: Option<&str>,
+) -> Result<(Vec<Vec<String>>, FileFormat)> {
+    match FileFormat::of(path) {
+        FileFormat::Xlsx(_) => {
+            let (matrix, sheet) = xlsx::load(path, name)?;
+            Ok((matrix, FileFormat::Xlsx(sheet)))
+        }
+        FileFormat::Sqlite(_) => {
+            let (matrix, source) = sqlite::load(path, name)?;
+            Ok((matrix, FileFormat::Sqlite(source)))
+        }
+        _ => Err(eyre!("{} has no sheets or tables", path.display())),
+    }
+}
@@ -0,0 +1,307 @@
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+
+use crate::{codec, schema::Violation, utils::column_name};
+
+/// Where the columns of a fixed width file are on its lines.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    pub fields: Vec<Field>,
+    /// If every line was padded to the end of the last column.
+    pub padded: bool,
+    /// If the lines ended with `\r\n`.
+    pub crlf: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Field {
+    /// The character of the line where the column starts.
+    pub start: usize,
+    /// How many characters the column has.
+    pub width: usize,
+    /// If the values are padded on the left, like numbers often are.
+    pub right: bool,
+    /// The same for the first line, often a name on top of numbers.
+    pub first_right: bool,
+    /// If the values are numbers padded with zeros on the left.
+    pub zeros: bool,
+}
+
+impl Field {
+    fn end(&self) -> usize {
+        self.start + self.width
+    }
+}
+
+impl Layout {
+    /// The fields of the layout and a field after them for every column it
+    /// does not have, as wide as their widest value.
+    fn fit(&self, matrix: &[Vec<String>]) -> Vec<Field> {
+        let mut fields = self.fields.clone();
+        let count = matrix.iter().map(|r| r.len()).max().unwrap_or(0);
+        for c in fields.len()..count {
+            let start = fields.last().map_or(0, |f| f.end() + 1);
+            let width = matrix
+                .iter()
+                .filter_map(|r| r.get(c))
+                .map(|v| v.chars().count())
+                .max()
+                .unwrap_or(0);
+            fields.push(Field {
+                start,
+                width,
+                right: false,
+                first_right: false,
+                zeros: false,
+            });
+        }
+        fields
+    }
+}
+
+/// Read a fixed width file, split by the widths of its columns, or where
+/// the columns line up when they are not given. Values are read without the
+/// spaces around them, and a line longer than the layout keeps the rest on
+/// its last column.
+pub fn load(
+    path: &Path,
+    widths: Option<&[usize]>,
+) -> Result<(Vec<Vec<String>>, Layout)> {
+    Ok(parse(&codec::read_to_string(path)?, widths))
+}
+
+fn parse(text: &str, widths: Option<&[usize]>) -> (Vec<Vec<String>>, Layout) {
+    let lines: Vec<Vec<char>> =
+        text.lines().map(|l| l.chars().collect()).collect();
+    let spans = match widths {
+        Some(widths) => widths
+            .iter()
+            .scan(0, |start, &width| {
+                let span = (*start, width);
+                *start += width;
+                Some(span)
+            })
+            .collect(),
+        None => detect(&lines),
+    };
+
+    // the last column takes the rest of the line
+    let slice = |line: &[char], f: usize| -> String {
+        let (start, width) = spans[f];
+        let end = match f + 1 == spans.len() {
+            true => line.len(),
+            false => (start + width).min(line.len()),
+        };
+        line.get(start..end.max(start)).unwrap_or_default().iter().collect()
+    };
+
+    let fields = (0..spans.len())
+        .map(|f| {
+            let (start, width) = spans[f];
+            let raw: Vec<String> = lines.iter().map(|l| slice(l, f)).collect();
+            // the first line is left out when it is a name on top of them
+            let rest = raw.get(1..).unwrap_or_default();
+            let right = is_right(&raw, width) || is_right(rest, width);
+            let zeros = has_zeros(&raw) || has_zeros(rest);
+            let first_right = match raw.first() {
+                Some(first) if !first.trim().is_empty() => {
+                    is_right(std::slice::from_ref(first), width)
+                }
+                _ => right,
+            };
+            Field { start, width, right, first_right, zeros }
+        })
+        .collect::<Vec<Field>>();
+
+    let end = fields.last().map_or(0, |f| f.end());
+    let layout = Layout {
+        padded: !lines.is_empty() && lines.iter().all(|l| l.len() == end),
+        crlf: text.contains("\r\n"),
+        fields,
+    };
+    let matrix = lines
+        .iter()
+        .map(|line| {
+            (0..spans.len())
+                .map(|f| slice(line, f).trim().to_string())
+                .collect()
+        })
+        .collect();
+    (matrix, layout)
+}
+
+/// Write the table padded to the widths of the layout. Values wider than
+/// their column are cut, [`overflows`] tells which.
+pub fn to_string(layout: &Layout, matrix: &[Vec<String>]) -> String {
+    let fields = layout.fit(matrix);
+    let newline = if layout.crlf { "\r\n" } else { "\n" };
+    let mut text = String::new();
+    for (r, row) in matrix.iter().enumerate() {
+        let mut line = String::new();
+        let mut len = 0;
+        for (c, field) in fields.iter().enumerate() {
+            let value: String = row
+                .get(c)
+                .map_or("", |v| v.as_str())
+                .chars()
+                .take(field.width)
+                .collect();
+            let pad = field.width - value.chars().count();
+            let gap = field.start.saturating_sub(len);
+            line.push_str(&" ".repeat(gap));
+            let digits =
+                !value.is_empty() && value.chars().all(|c| c.is_ascii_digit());
+            match if r == 0 { field.first_right } else { field.right } {
+                _ if r > 0 && field.zeros && digits => {
+                    line.push_str(&"0".repeat(pad));
+                    line.push_str(&value);
+                }
+                true => {
+                    line.push_str(&" ".repeat(pad));
+                    line.push_str(&value);
+                }
+                false => {
+                    line.push_str(&value);
+                    line.push_str(&" ".repeat(pad));
+                }
+            }
+            len += gap + field.width;
+        }
+        if !layout.padded {
+            line.truncate(line.trim_end().len());
+        }
+        text.push_str(&line);
+        text.push_str(newline);
+    }
+    text
+}
+
+/// The cells wider than their column on the layout, which are cut when the
+/// table is written.
+pub fn overflows(layout: &Layout, matrix: &[Vec<String>]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (r, row) in matrix.iter().enumerate() {
+        for (c, field) in layout.fields.iter().enumerate() {
+            let len = row.get(c).map_or(0, |v| v.chars().count());
+            if len > field.width {
+                violations.push(Violation {
+                    cell: Some((r, c)),
+                    message: format!(
+                        "{}{}: {len} characters do not fit on a column of {}",
+                        column_name(c),
+                        r + 1,
+                        field.width
+                    ),
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// If the values are padded on the left to the end of their column.
+fn is_right(values: &[String], width: usize) -> bool {
+    let values: Vec<&String> =
+        values.iter().filter(|v| !v.trim().is_empty()).collect();
+    values.iter().any(|v| v.starts_with(' '))
+        && values
+            .iter()
+            .all(|v| v.chars().count() == width && !v.ends_with(' '))
+}
+
+/// If the values are digits with zeros on the left to fill their column.
+fn has_zeros(values: &[String]) -> bool {
+    let values: Vec<&String> =
+        values.iter().filter(|v| !v.trim().is_empty()).collect();
+    let width = values.first().map_or(0, |v| v.len());
+    values.iter().any(|v| v.starts_with('0') && v.len() > 1)
+        && values
+            .iter()
+            .all(|v| v.len() == width && v.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// The start and width of the columns of lines that line up, split where
+/// every line has a space or has ended.
+fn detect(lines: &[Vec<char>]) -> Vec<(usize, usize)> {
+    let len = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+    let blank =
+        |i: usize| lines.iter().all(|l| l.get(i).is_none_or(|c| *c == ' '));
+
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut start = None;
+    for i in 0..=len {
+        match (start, i == len || blank(i)) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                spans.push((s, i - s));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if spans.is_empty() {
+        spans.push((0, len));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<Vec<char>> {
+        text.lines().map(|l| l.chars().collect()).collect()
+    }
+
+    #[test]
+    fn detect_columns() {
+        let text = "name   qty\nann      3\nbob     12\n";
+        assert_eq!(detect(&lines(text)), [(0, 4), (7, 3)]);
+    }
+
+    #[test]
+    fn detect_short_lines_and_no_gaps() {
+        assert_eq!(detect(&lines("ab cd\nef")), [(0, 2), (3, 2)]);
+        assert_eq!(detect(&lines("abc\nde")), [(0, 3)]);
+        assert_eq!(detect(&[]), [(0, 0)]);
+    }
+
+    #[test]
+    fn parse_detected_layout() {
+        let text = "name   qty\nann      3\nbob     12\n";
+        let (matrix, layout) = parse(text, None);
+
+        assert_eq!(matrix, [["name", "qty"], ["ann", "3"], ["bob", "12"]]);
+        assert!(!layout.fields[0].right);
+        assert!(layout.fields[1].right);
+        assert!(!layout.fields[1].first_right);
+        assert!(layout.padded);
+        assert_eq!(to_string(&layout, &matrix), text);
+    }
+
+    #[test]
+    fn parse_given_widths() {
+        let text = "00120ab\r\n00034cdef\r\n";
+        let (matrix, layout) = parse(text, Some(&[5, 2]));
+
+        assert_eq!(matrix, [["00120", "ab"], ["00034", "cdef"]]);
+        assert!(layout.fields[0].zeros);
+        assert!(layout.crlf);
+        assert!(!layout.padded);
+    }
+
+    #[test]
+    fn write_pads_and_reports_overflows() {
+        let (_, layout) = parse("id  n\n007 a\n", None);
+        let matrix: Vec<Vec<String>> = [["id", "n"], ["12", "bc"]]
+            .iter()
+            .map(|r| r.map(String::from).to_vec())
+            .collect();
+
+        assert_eq!(to_string(&layout, &matrix), "id  n\n012 b\n");
+        let overflows = overflows(&layout, &matrix);
+        assert_eq!(overflows.len(), 1);
+        assert_eq!(overflows[0].cell, Some((1, 1)));
+    }
+}
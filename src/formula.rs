@@ -0,0 +1,1089 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use itertools::Itertools;
+
+use crate::utils::{column_index, column_name};
+
+/// A cell on the matrix, (row, col).
+pub type Cell = (usize, usize);
+
+/// The result of evaluating an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Empty,
+    /// Returned by functions like `SPLIT`, items are picked with `[i]`.
+    List(Vec<Value>),
+    /// Spreadsheet style errors, like `#DIV/0!`.
+    Error(&'static str),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => {
+                write!(f, "{}", *n as i64)
+            }
+            Value::Number(n) => write!(f, "{n}"),
+            Value::Text(s) => write!(f, "{s}"),
+            Value::Bool(true) => write!(f, "TRUE"),
+            Value::Bool(false) => write!(f, "FALSE"),
+            Value::Empty => Ok(()),
+            Value::List(items) => write!(f, "{}", items.iter().join(", ")),
+            Value::Error(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Value {
+    /// Read the raw value of a cell that is not a formula.
+    pub fn from_cell(raw: &str) -> Self {
+        if raw.is_empty() {
+            return Value::Empty;
+        }
+        match raw.trim().parse::<f64>() {
+            Ok(n) if n.is_finite() => Value::Number(n),
+            _ => Value::Text(raw.to_string()),
+        }
+    }
+
+    pub fn number(&self) -> Result<f64, Value> {
+        match self {
+            Value::Number(n) => Ok(*n),
+            Value::Bool(b) => Ok(*b as u8 as f64),
+            Value::Empty => Ok(0.0),
+            Value::Text(s) => {
+                s.trim().parse().map_err(|_| Value::Error("#VALUE!"))
+            }
+            Value::List(_) => Err(Value::Error("#VALUE!")),
+            Value::Error(_) => Err(self.clone()),
+        }
+    }
+
+    pub fn text(&self) -> Result<String, Value> {
+        match self {
+            Value::Error(_) => Err(self.clone()),
+            v => Ok(v.to_string()),
+        }
+    }
+
+    pub fn truthy(&self) -> Result<bool, Value> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            Value::Text(s) if s.eq_ignore_ascii_case("true") => Ok(true),
+            Value::Text(s) if s.eq_ignore_ascii_case("false") => Ok(false),
+            v => v.number().map(|n| n != 0.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Ref(Cell),
+    /// An inclusive range of cells, from the top left to the bottom right.
+    Range(Cell, Cell),
+    /// An identifier that is not a cell reference.
+    Name(String),
+    Call(String, Vec<Expr>),
+    /// `list[i]`, picks an item of a list, starting at 0.
+    Index(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Colon,
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_digit() || chars[i] == '.')
+                {
+                    i += 1;
+                }
+                // exponent, like 1e-3
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && matches!(chars[j], '+' | '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let number: String = chars[start..i].iter().collect();
+                let n = number
+                    .parse()
+                    .map_err(|_| format!("Invalid number {number}"))?;
+                tokens.push(Token::Number(n));
+            }
+            '"' => {
+                // strings are quoted with `"`, and `""` is an escaped quote
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err("Unterminated string".into()),
+                        Some('"') if chars.get(i + 1) == Some(&'"') => {
+                            text.push('"');
+                            i += 2;
+                        }
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(c) => {
+                            text.push(*c);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric()
+                        || matches!(chars[i], '_' | '$' | '.'))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '[' => {
+                tokens.push(Token::LBracket);
+                i += 1;
+            }
+            ']' => {
+                tokens.push(Token::RBracket);
+                i += 1;
+            }
+            ',' | ';' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            ':' => {
+                tokens.push(Token::Colon);
+                i += 1;
+            }
+            _ => {
+                let two: String =
+                    chars[i..(i + 2).min(chars.len())].iter().collect();
+                let op = match two.as_str() {
+                    "<>" | "!=" => "<>",
+                    "<=" => "<=",
+                    ">=" => ">=",
+                    "==" => "=",
+                    _ => "",
+                };
+                if !op.is_empty() {
+                    tokens.push(Token::Op(op));
+                    i += 2;
+                    continue;
+                }
+                let op = match c {
+                    '+' => "+",
+                    '-' => "-",
+                    '*' => "*",
+                    '/' => "/",
+                    '^' => "^",
+                    '&' => "&",
+                    '=' => "=",
+                    '<' => "<",
+                    '>' => ">",
+                    _ => return Err(format!("Unexpected {c:?}")),
+                };
+                tokens.push(Token::Op(op));
+                i += 1;
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a cell reference like `B12` or `$B$12`.
+fn parse_ref(ident: &str) -> Option<Cell> {
+    let ident = ident.replace('$', "");
+    let split = ident.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = ident.split_at(split);
+    let col = column_index(letters)?;
+    let row: usize = digits.parse().ok()?;
+    // spreadsheet rows start at 1
+    Some((row.checked_sub(1)?, col))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            Some(t) => Err(format!("Expected {token:?}, found {t:?}")),
+            None => Err(format!("Expected {token:?}")),
+        }
+    }
+
+    /// Parse a binary operation where `ops` have the same precedence, and
+    /// their operands are parsed by `operand`.
+    fn binary(
+        &mut self,
+        ops: &[(&str, Op)],
+        operand: fn(&mut Self) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut lhs = operand(self)?;
+        while let Some(Token::Op(o)) = self.peek() {
+            let Some((_, op)) = ops.iter().find(|(s, _)| s == o) else {
+                break;
+            };
+            let op = *op;
+            self.pos += 1;
+            let rhs = operand(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[
+                ("=", Op::Eq),
+                ("<>", Op::Ne),
+                ("<", Op::Lt),
+                (">", Op::Gt),
+                ("<=", Op::Le),
+                (">=", Op::Ge),
+            ],
+            Self::concat,
+        )
+    }
+
+    fn concat(&mut self) -> Result<Expr, String> {
+        self.binary(&[("&", Op::Concat)], Self::additive)
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        self.binary(&[("+", Op::Add), ("-", Op::Sub)], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        self.binary(&[("*", Op::Mul), ("/", Op::Div)], Self::power)
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        self.binary(&[("^", Op::Pow)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some(Token::Op("-")) => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            }
+            Some(Token::Op("+")) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    /// A primary expression followed by any number of `[i]`.
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut e = self.primary()?;
+        while let Some(Token::LBracket) = self.peek() {
+            self.pos += 1;
+            let index = self.expr()?;
+            self.expect(Token::RBracket)?;
+            e = Expr::Index(Box::new(e), Box::new(index));
+        }
+        Ok(e)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Text(s)) => Ok(Expr::Text(s)),
+            Some(Token::LParen) => {
+                let e = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(e)
+            }
+            Some(Token::Ident(name)) => {
+                if let Some(Token::LParen) = self.peek() {
+                    self.pos += 1;
+                    let mut args = Vec::new();
+                    if let Some(Token::RParen) = self.peek() {
+                        self.pos += 1;
+                    } else {
+                        loop {
+                            args.push(self.expr()?);
+                            match self.next() {
+                                Some(Token::Comma) => continue,
+                                Some(Token::RParen) => break,
+                                _ => return Err("Expected , or )".into()),
+                            }
+                        }
+                    }
+                    return Ok(Expr::Call(name.to_ascii_uppercase(), args));
+                }
+
+                if name.eq_ignore_ascii_case("true") {
+                    return Ok(Expr::Bool(true));
+                }
+                if name.eq_ignore_ascii_case("false") {
+                    return Ok(Expr::Bool(false));
+                }
+
+                let Some(start) = parse_ref(&name) else {
+                    return Ok(Expr::Name(name));
+                };
+                if let Some(Token::Colon) = self.peek() {
+                    self.pos += 1;
+                    let end = match self.next() {
+                        Some(Token::Ident(end)) => parse_ref(&end),
+                        _ => None,
+                    }
+                    .ok_or("Invalid range")?;
+                    let top_left = (start.0.min(end.0), start.1.min(end.1));
+                    let bottom_right = (start.0.max(end.0), start.1.max(end.1));
+                    return Ok(Expr::Range(top_left, bottom_right));
+                }
+                Ok(Expr::Ref(start))
+            }
+            Some(t) => Err(format!("Unexpected {t:?}")),
+            None => Err("Unexpected end of formula".into()),
+        }
+    }
+}
+
+impl Op {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Pow => "^",
+            Op::Concat => "&",
+            Op::Eq => "=",
+            Op::Ne => "<>",
+            Op::Lt => "<",
+            Op::Gt => ">",
+            Op::Le => "<=",
+            Op::Ge => ">=",
+        }
+    }
+
+    /// The higher, the tighter the operator binds.
+    fn precedence(&self) -> u8 {
+        match self {
+            Op::Eq | Op::Ne | Op::Lt | Op::Gt | Op::Le | Op::Ge => 0,
+            Op::Concat => 1,
+            Op::Add | Op::Sub => 2,
+            Op::Mul | Op::Div => 3,
+            Op::Pow => 4,
+        }
+    }
+}
+
+/// Write the expression back as formula source, cells are written A1 style.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cell =
+            |(row, col): Cell| format!("{}{}", column_name(col), row + 1);
+        match self {
+            Expr::Number(n) => write!(f, "{}", Value::Number(*n)),
+            Expr::Text(s) => write!(f, "\"{}\"", s.replace('"', "\"\"")),
+            Expr::Bool(b) => write!(f, "{}", Value::Bool(*b)),
+            Expr::Ref(c) => write!(f, "{}", cell(*c)),
+            Expr::Range(a, b) => write!(f, "{}:{}", cell(*a), cell(*b)),
+            Expr::Name(name) => write!(f, "{name}"),
+            Expr::Call(name, args) => {
+                write!(f, "{name}({})", args.iter().join(", "))
+            }
+            Expr::Index(e, i) => match **e {
+                Expr::Binary(..) | Expr::Neg(_) => write!(f, "({e})[{i}]"),
+                _ => write!(f, "{e}[{i}]"),
+            },
+            Expr::Neg(e) => match **e {
+                Expr::Binary(..) => write!(f, "-({e})"),
+                _ => write!(f, "-{e}"),
+            },
+            Expr::Binary(op, a, b) => {
+                // wrap operands that bind looser than `op`, the right one
+                // also when it binds the same, since operators are left
+                // associative
+                let wrap = |e: &Expr, right: bool| match e {
+                    Expr::Binary(o, ..)
+                        if o.precedence() < op.precedence()
+                            || (right && o.precedence() == op.precedence()) =>
+                    {
+                        format!("({e})")
+                    }
+                    e => e.to_string(),
+                };
+                write!(
+                    f,
+                    "{} {} {}",
+                    wrap(a, false),
+                    op.symbol(),
+                    wrap(b, true)
+                )
+            }
+        }
+    }
+}
+
+/// Parse an expression, without the leading `=` of a formula.
+pub fn parse(src: &str) -> Result<Expr, String> {
+    let mut parser = Parser { tokens: tokenize(src)?, pos: 0 };
+    let expr = parser.expr()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(t) => Err(format!("Unexpected {t:?}")),
+    }
+}
+
+/// If the raw value of a cell is a formula, return it without the `=`.
+pub fn formula_of(raw: &str) -> Option<&str> {
+    raw.strip_prefix('=').filter(|f| !f.trim().is_empty())
+}
+
+/// Where an expression gets the values it refers to.
+pub trait Context {
+    fn cell(&self, cell: Cell) -> Value;
+
+    /// The number of rows and columns, ranges only read the cells within.
+    fn size(&self) -> Cell;
+
+    fn name(&self, _name: &str) -> Value {
+        Value::Error("#NAME?")
+    }
+}
+
+impl Expr {
+    /// The cells and ranges this expression reads.
+    pub fn references(&self, out: &mut Vec<(Cell, Cell)>) {
+        match self {
+            Expr::Ref(c) => out.push((*c, *c)),
+            Expr::Range(a, b) => out.push((*a, *b)),
+            Expr::Call(_, args) => args.iter().for_each(|a| a.references(out)),
+            Expr::Index(e, i) => {
+                e.references(out);
+                i.references(out);
+            }
+            Expr::Neg(e) => e.references(out),
+            Expr::Binary(_, a, b) => {
+                a.references(out);
+                b.references(out);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn eval(&self, ctx: &dyn Context) -> Value {
+        match self {
+            Expr::Number(n) => Value::Number(*n),
+            Expr::Text(s) => Value::Text(s.clone()),
+            Expr::Bool(b) => Value::Bool(*b),
+            Expr::Ref(c) => ctx.cell(*c),
+            // a range is only meaningful as a function argument
+            Expr::Range(..) => Value::Error("#VALUE!"),
+            Expr::Name(name) => ctx.name(name),
+            Expr::Neg(e) => match e.eval(ctx).number() {
+                Ok(n) => Value::Number(-n),
+                Err(e) => e,
+            },
+            Expr::Binary(op, a, b) => binary(*op, a.eval(ctx), b.eval(ctx)),
+            Expr::Call(name, args) => call(name, args, ctx),
+            Expr::Index(e, i) => {
+                let i = match i.eval(ctx).number() {
+                    Ok(i) if i >= 0.0 => i as usize,
+                    Ok(_) => return Value::Error("#REF!"),
+                    Err(e) => return e,
+                };
+                match e.eval(ctx) {
+                    Value::List(mut items) if i < items.len() => {
+                        items.swap_remove(i)
+                    }
+                    Value::List(_) => Value::Error("#REF!"),
+                    e @ Value::Error(_) => e,
+                    _ => Value::Error("#VALUE!"),
+                }
+            }
+        }
+    }
+
+    /// The names this expression reads.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Expr::Name(name) => vec![name.as_str()],
+            Expr::Call(_, args) => {
+                args.iter().flat_map(|a| a.names()).collect()
+            }
+            Expr::Index(e, i) => [e.names(), i.names()].concat(),
+            Expr::Neg(e) => e.names(),
+            Expr::Binary(_, a, b) => [a.names(), b.names()].concat(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Replace every name with the expression returned by `f`, if any.
+    pub fn replace_names(self, f: &impl Fn(&str) -> Option<Expr>) -> Self {
+        let boxed = |e: Box<Expr>| Box::new(e.replace_names(f));
+        match self {
+            Expr::Name(name) => f(&name).unwrap_or(Expr::Name(name)),
+            Expr::Call(name, args) => Expr::Call(
+                name,
+                args.into_iter().map(|a| a.replace_names(f)).collect(),
+            ),
+            Expr::Index(e, i) => Expr::Index(boxed(e), boxed(i)),
+            Expr::Neg(e) => Expr::Neg(boxed(e)),
+            Expr::Binary(op, a, b) => Expr::Binary(op, boxed(a), boxed(b)),
+            e => e,
+        }
+    }
+
+    /// Evaluate as a function argument, ranges expand to all their cells
+    /// that are on the matrix.
+    fn eval_flat(&self, ctx: &dyn Context) -> Vec<Value> {
+        match self {
+            Expr::Range(a, b) => {
+                cells((*a, *b), ctx.size()).map(|cell| ctx.cell(cell)).collect()
+            }
+            e => vec![e.eval(ctx)],
+        }
+    }
+}
+
+/// The cells of a range, cut to a matrix of `size` rows and columns.
+fn cells(
+    ((r0, c0), (r1, c1)): (Cell, Cell),
+    (rows, cols): Cell,
+) -> impl Iterator<Item = Cell> {
+    let columns = c0..(c1 + 1).min(cols);
+    (r0..(r1 + 1).min(rows))
+        .flat_map(move |r| columns.clone().map(move |c| (r, c)))
+}
+
+fn binary(op: Op, a: Value, b: Value) -> Value {
+    if let Value::Error(_) = a {
+        return a;
+    }
+    if let Value::Error(_) = b {
+        return b;
+    }
+
+    let numbers = || Ok::<_, Value>((a.number()?, b.number()?));
+    let arithmetic = |f: fn(f64, f64) -> f64| match numbers() {
+        Ok((x, y)) => {
+            let n = f(x, y);
+            if n.is_finite() {
+                Value::Number(n)
+            } else {
+                Value::Error("#NUM!")
+            }
+        }
+        Err(e) => e,
+    };
+
+    match op {
+        Op::Add => arithmetic(|x, y| x + y),
+        Op::Sub => arithmetic(|x, y| x - y),
+        Op::Mul => arithmetic(|x, y| x * y),
+        Op::Div => match numbers() {
+            Ok((_, 0.0)) => Value::Error("#DIV/0!"),
+            Ok((x, y)) => Value::Number(x / y),
+            Err(e) => e,
+        },
+        Op::Pow => arithmetic(f64::powf),
+        Op::Concat => Value::Text(format!("{a}{b}")),
+        _ => {
+            // numbers are compared as numbers, everything else as text
+            let ordering = match (&a, &b) {
+                (Value::Text(_), _) | (_, Value::Text(_)) => a
+                    .to_string()
+                    .to_lowercase()
+                    .cmp(&b.to_string().to_lowercase()),
+                _ => match numbers() {
+                    Ok((x, y)) => {
+                        x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal)
+                    }
+                    Err(e) => return e,
+                },
+            };
+            Value::Bool(match op {
+                Op::Eq => ordering.is_eq(),
+                Op::Ne => ordering.is_ne(),
+                Op::Lt => ordering.is_lt(),
+                Op::Gt => ordering.is_gt(),
+                Op::Le => ordering.is_le(),
+                _ => ordering.is_ge(),
+            })
+        }
+    }
+}
+
+/// The numbers of the arguments, ranges expanded. Like spreadsheets, text
+/// and empty cells inside ranges are skipped.
+fn numbers(args: &[Expr], ctx: &dyn Context) -> Result<Vec<f64>, Value> {
+    let mut numbers = Vec::new();
+    for arg in args {
+        let in_range = matches!(arg, Expr::Range(..));
+        for v in arg.eval_flat(ctx) {
+            match v {
+                Value::Error(_) => return Err(v),
+                Value::Text(_) | Value::Empty if in_range => {}
+                Value::Text(ref s) if s.trim().parse::<f64>().is_err() => {}
+                v => numbers.push(v.number()?),
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+fn call(name: &str, args: &[Expr], ctx: &dyn Context) -> Value {
+    let arg = |i: usize| args.get(i).map_or(Value::Empty, |a| a.eval(ctx));
+    let number = |i: usize| arg(i).number();
+    let text = |i: usize| arg(i).text();
+
+    let result: Result<Value, Value> = (|| {
+        Ok(match name {
+            "SUM" => Value::Number(numbers(args, ctx)?.iter().sum()),
+            "AVERAGE" | "AVG" => {
+                let n = numbers(args, ctx)?;
+                if n.is_empty() {
+                    return Err(Value::Error("#DIV/0!"));
+                }
+                Value::Number(n.iter().sum::<f64>() / n.len() as f64)
+            }
+            "MIN" => Value::Number(
+                numbers(args, ctx)?.into_iter().reduce(f64::min).unwrap_or(0.0),
+            ),
+            "MAX" => Value::Number(
+                numbers(args, ctx)?.into_iter().reduce(f64::max).unwrap_or(0.0),
+            ),
+            "COUNT" => Value::Number(numbers(args, ctx)?.len() as f64),
+            "COUNTA" => Value::Number(
+                args.iter()
+                    .flat_map(|a| a.eval_flat(ctx))
+                    .filter(|v| *v != Value::Empty)
+                    .count() as f64,
+            ),
+            "CONCAT" | "CONCATENATE" => {
+                let mut s = String::new();
+                for v in args.iter().flat_map(|a| a.eval_flat(ctx)) {
+                    s.push_str(&v.text()?);
+                }
+                Value::Text(s)
+            }
+            "IF" => {
+                if arg(0).truthy()? {
+                    arg(1)
+                } else {
+                    arg(2)
+                }
+            }
+            "AND" => Value::Bool(
+                args.iter()
+                    .flat_map(|a| a.eval_flat(ctx))
+                    .map(|v| v.truthy())
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .all(|b| b),
+            ),
+            "OR" => Value::Bool(
+                args.iter()
+                    .flat_map(|a| a.eval_flat(ctx))
+                    .map(|v| v.truthy())
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .any(|b| b),
+            ),
+            "NOT" => Value::Bool(!arg(0).truthy()?),
+            "ABS" => Value::Number(number(0)?.abs()),
+            "ROUND" => {
+                let factor = 10f64.powi(number(1)? as i32);
+                Value::Number((number(0)? * factor).round() / factor)
+            }
+            "LEN" => Value::Number(text(0)?.chars().count() as f64),
+            "UPPER" => Value::Text(text(0)?.to_uppercase()),
+            "LOWER" => Value::Text(text(0)?.to_lowercase()),
+            "TRIM" => Value::Text(text(0)?.trim().to_string()),
+            "SPLIT" => Value::List(
+                text(0)?
+                    .split(text(1)?.as_str())
+                    .map(|s| Value::Text(s.to_string()))
+                    .collect(),
+            ),
+            _ => return Err(Value::Error("#NAME?")),
+        })
+    })();
+
+    result.unwrap_or_else(|e| e)
+}
+
+/// The number of rows and columns of a matrix, its longest row.
+fn size(matrix: &[Vec<String>]) -> Cell {
+    (matrix.len(), matrix.iter().map(|r| r.len()).max().unwrap_or(0))
+}
+
+/// The formulas of a matrix, with their computed values.
+///
+/// Values are computed lazily and memoized, so a formula is only evaluated
+/// after the cells it depends on. Those are computed first, from the deepest
+/// one, so a long chain of references does not nest the evaluation. A
+/// formula that ends up depending on itself evaluates to `#CYCLE!`.
+#[derive(Default)]
+pub struct Formulas {
+    /// Parsed formula of each formula cell.
+    parsed: HashMap<Cell, Result<Expr, String>>,
+    /// The ranges each formula reads, the edges of the dependency graph.
+    references: HashMap<Cell, Vec<(Cell, Cell)>>,
+    values: RefCell<HashMap<Cell, Value>>,
+    /// Formulas being evaluated, to detect cycles.
+    evaluating: RefCell<HashSet<Cell>>,
+    /// The number of rows and columns of the matrix.
+    size: Cell,
+}
+
+struct SheetContext<'a> {
+    formulas: &'a Formulas,
+    matrix: &'a [Vec<String>],
+}
+
+impl Context for SheetContext<'_> {
+    fn cell(&self, cell: Cell) -> Value {
+        self.formulas.value(self.matrix, cell)
+    }
+
+    fn size(&self) -> Cell {
+        self.formulas.size
+    }
+}
+
+impl Formulas {
+    pub fn new(matrix: &[Vec<String>]) -> Self {
+        let mut formulas =
+            Formulas { size: size(matrix), ..Default::default() };
+        for (r, row) in matrix.iter().enumerate() {
+            for c in 0..row.len() {
+                formulas.parse_cell(matrix, (r, c));
+            }
+        }
+        formulas
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parsed.is_empty()
+    }
+
+    pub fn is_formula(&self, cell: Cell) -> bool {
+        self.parsed.contains_key(&cell)
+    }
+
+    fn parse_cell(&mut self, matrix: &[Vec<String>], cell: Cell) {
+        self.parsed.remove(&cell);
+        self.references.remove(&cell);
+
+        let raw = matrix.get(cell.0).and_then(|r| r.get(cell.1));
+        let Some(formula) = raw.and_then(|raw| formula_of(raw)) else {
+            return;
+        };
+
+        let parsed = parse(formula);
+        if let Ok(expr) = &parsed {
+            let mut refs = Vec::new();
+            expr.references(&mut refs);
+            self.references.insert(cell, refs);
+        }
+        self.parsed.insert(cell, parsed);
+    }
+
+    /// The value of a cell, evaluating it if it is a formula.
+    pub fn value(&self, matrix: &[Vec<String>], cell: Cell) -> Value {
+        let Some(parsed) = self.parsed.get(&cell) else {
+            let raw = matrix.get(cell.0).and_then(|r| r.get(cell.1));
+            return raw.map_or(Value::Empty, |raw| Value::from_cell(raw));
+        };
+
+        if let Some(v) = self.values.borrow().get(&cell) {
+            return v.clone();
+        }
+        if !self.evaluating.borrow_mut().insert(cell) {
+            return Value::Error("#CYCLE!");
+        }
+
+        for dependency in self.unevaluated(cell) {
+            self.value(matrix, dependency);
+        }
+        let value = match parsed {
+            Ok(expr) => expr.eval(&SheetContext { formulas: self, matrix }),
+            Err(_) => Value::Error("#ERROR!"),
+        };
+
+        self.evaluating.borrow_mut().remove(&cell);
+        self.values.borrow_mut().insert(cell, value.clone());
+        value
+    }
+
+    /// Evaluate an expression that is not on the matrix, it can read any
+    /// cell.
+    pub fn eval(&self, matrix: &[Vec<String>], expr: &Expr) -> Value {
+        let mut refs = Vec::new();
+        expr.references(&mut refs);
+        for range in refs {
+            for cell in self.formulas_in(range) {
+                self.value(matrix, cell);
+            }
+        }
+        expr.eval(&SheetContext { formulas: self, matrix })
+    }
+
+    /// The formula cells of a range.
+    fn formulas_in(
+        &self,
+        range: (Cell, Cell),
+    ) -> impl Iterator<Item = Cell> + '_ {
+        cells(range, self.size).filter(|cell| self.parsed.contains_key(cell))
+    }
+
+    /// The formulas `cell` reads, directly or through other formulas, that
+    /// have no value yet and are not being evaluated, each one after the
+    /// formulas it reads.
+    fn unevaluated(&self, cell: Cell) -> Vec<Cell> {
+        let values = self.values.borrow();
+        let evaluating = self.evaluating.borrow();
+        let mut order = Vec::new();
+        let mut seen = HashSet::from([cell]);
+        let mut stack = vec![(cell, false)];
+        while let Some((formula, read)) = stack.pop() {
+            if read {
+                order.push(formula);
+                continue;
+            }
+            stack.push((formula, true));
+            let refs = self.references.get(&formula).into_iter().flatten();
+            for reads in refs.flat_map(|range| self.formulas_in(*range)) {
+                if !values.contains_key(&reads)
+                    && !evaluating.contains(&reads)
+                    && seen.insert(reads)
+                {
+                    stack.push((reads, false));
+                }
+            }
+        }
+        // the last one is `cell` itself
+        order.pop();
+        order
+    }
+
+    /// The parse error of a formula cell, if it could not be parsed.
+    pub fn error(&self, cell: Cell) -> Option<&str> {
+        self.parsed.get(&cell)?.as_ref().err().map(|e| e.as_str())
+    }
+
+    /// Formula cells that read `cell`, directly or through other formulas.
+    fn dependents(&self, cell: Cell) -> HashSet<Cell> {
+        let mut found = HashSet::new();
+        let mut queue = vec![cell];
+
+        while let Some(changed) = queue.pop() {
+            for (formula, refs) in &self.references {
+                let reads = refs.iter().any(|(a, b)| {
+                    (a.0..=b.0).contains(&changed.0)
+                        && (a.1..=b.1).contains(&changed.1)
+                });
+                if reads && found.insert(*formula) {
+                    queue.push(*formula);
+                }
+            }
+        }
+        found
+    }
+
+    /// Recalculate after a single cell changed, only the cell and the
+    /// formulas that depend on it are computed again.
+    pub fn update(&mut self, matrix: &[Vec<String>], cell: Cell) {
+        self.size = size(matrix);
+        self.parse_cell(matrix, cell);
+
+        let mut values = self.values.borrow_mut();
+        values.remove(&cell);
+        for dependent in self.dependents(cell) {
+            values.remove(&dependent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|r| r.iter().map(|v| v.to_string()).collect()).collect()
+    }
+
+    fn eval(src: &str) -> Value {
+        Formulas::default().eval(&[], &parse(src).unwrap())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1+2*3^2"), Value::Number(19.0));
+        assert_eq!(eval("(1+2)*3"), Value::Number(9.0));
+        assert_eq!(eval("10-4-3"), Value::Number(3.0));
+        assert_eq!(eval("1+2&3"), Value::Text("33".into()));
+        assert_eq!(eval("1+1=2"), Value::Bool(true));
+        assert_eq!(eval("-2+3"), Value::Number(1.0));
+    }
+
+    #[test]
+    fn display_parses_back() {
+        for src in ["1+2*3", "(1+2)*3", "SUM(A1:B2)-$C$3", "\"a\"&B1"] {
+            let expr = parse(src).unwrap();
+            assert_eq!(parse(&expr.to_string()).unwrap(), expr);
+        }
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(parse("B12"), Ok(Expr::Ref((11, 1))));
+        assert_eq!(parse("$AA$1"), Ok(Expr::Ref((0, 26))));
+        assert_eq!(parse("b2:a1"), Ok(Expr::Range((0, 0), (1, 1))));
+        assert_eq!(parse("A0"), Ok(Expr::Name("A0".into())));
+        assert!(parse("A1:").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse("1+").is_err());
+        assert!(parse("SUM(1").is_err());
+        assert!(parse("1 2").is_err());
+        assert_eq!(eval("1/0"), Value::Error("#DIV/0!"));
+        assert_eq!(eval("FOO(1)"), Value::Error("#NAME?"));
+        assert_eq!(eval("\"a\"+1"), Value::Error("#VALUE!"));
+        assert_eq!(eval("SPLIT(\"a,b\",\",\")[2]"), Value::Error("#REF!"));
+
+        let m = matrix(&[&["=1+", "=A1*2"]]);
+        let formulas = Formulas::new(&m);
+        assert_eq!(formulas.value(&m, (0, 0)), Value::Error("#ERROR!"));
+        assert_eq!(formulas.value(&m, (0, 1)), Value::Error("#ERROR!"));
+    }
+
+    #[test]
+    fn cycles() {
+        let m = matrix(&[&["=B1", "=A1", "=A1+1"], &["=A2", "", ""]]);
+        let formulas = Formulas::new(&m);
+        assert_eq!(formulas.value(&m, (0, 0)), Value::Error("#CYCLE!"));
+        assert_eq!(formulas.value(&m, (0, 2)), Value::Error("#CYCLE!"));
+        assert_eq!(formulas.value(&m, (1, 0)), Value::Error("#CYCLE!"));
+    }
+
+    #[test]
+    fn update_invalidates_dependents() {
+        let mut m = matrix(&[&["1", "=A1*2", "=B1+1", "=SUM(A1:C1)"]]);
+        let mut formulas = Formulas::new(&m);
+        assert_eq!(formulas.value(&m, (0, 2)), Value::Number(3.0));
+        assert_eq!(formulas.value(&m, (0, 3)), Value::Number(6.0));
+
+        m[0][0] = "5".into();
+        formulas.update(&m, (0, 0));
+        assert_eq!(formulas.value(&m, (0, 2)), Value::Number(11.0));
+        assert_eq!(formulas.value(&m, (0, 3)), Value::Number(26.0));
+
+        m[0][1] = "=A1".into();
+        formulas.update(&m, (0, 1));
+        assert_eq!(formulas.value(&m, (0, 2)), Value::Number(6.0));
+    }
+
+    #[test]
+    fn long_chains() {
+        let mut m = vec![vec!["1".to_string()]];
+        m.extend((1..20_000).map(|r| vec![format!("=A{r}+1")]));
+        let formulas = Formulas::new(&m);
+        assert_eq!(formulas.value(&m, (19_999, 0)), Value::Number(20_000.0));
+    }
+
+    #[test]
+    fn ranges_are_cut_to_the_matrix() {
+        let m = matrix(&[&["1", "2"], &["3", "=SUM(A1:ZZ1000000)"]]);
+        let formulas = Formulas::new(&m);
+        assert_eq!(formulas.value(&m, (1, 1)), Value::Error("#CYCLE!"));
+        let count = parse("COUNTA(A1:ZZ1000000)").unwrap();
+        assert_eq!(formulas.eval(&m, &count), Value::Number(4.0));
+        let sum = parse("SUM(A1:ZZ1000000)").unwrap();
+        let m = matrix(&[&["1", "2"], &["3", ""]]);
+        assert_eq!(Formulas::new(&m).eval(&m, &sum), Value::Number(6.0));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+/// Which rows are kept when joining two tables.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum JoinKind {
+    /// Only the rows with a match on both tables.
+    Inner,
+    /// Every row of the current table, with empty values when there is no
+    /// match.
+    #[default]
+    Left,
+    /// Every row of both tables.
+    Outer,
+}
+
+/// The result of a join.
+#[derive(Clone, Debug, Default)]
+pub struct Joined {
+    pub matrix: Vec<Vec<String>>,
+    /// The row of the current table each row of the matrix comes from,
+    /// `None` for the rows only on the other table.
+    pub rows: Vec<Option<usize>>,
+    /// Keys of the current table not found on the other one, the values
+    /// of many key columns are shown like `a/b`.
+    pub unmatched_left: Vec<String>,
+    /// Keys of the other table not found on the current one.
+    pub unmatched_right: Vec<String>,
+}
+
+/// Join `right` to `left`, matching the values of the `left_keys` columns
+/// with the values of the `right_keys` columns, and append the `columns` of
+/// `right`. A row matching many rows is repeated once for each of them. When
+/// there is a header, the names of the columns are appended to it.
+pub fn join(
+    left: &[Vec<String>],
+    right: &[Vec<String>],
+    has_header: bool,
+    (left_keys, right_keys): (&[usize], &[usize]),
+    columns: &[usize],
+    kind: JoinKind,
+) -> Joined {
+    let start = if has_header { 1 } else { 0 };
+    let width = left.iter().map(|r| r.len()).max().unwrap_or(0);
+    let cell = |row: &Vec<String>, col: usize| {
+        row.get(col).map_or(String::new(), |v| v.clone())
+    };
+    let pick = |row: &Vec<String>| -> Vec<String> {
+        columns.iter().map(|c| cell(row, *c)).collect()
+    };
+    let key_of = |row: &Vec<String>, keys: &[usize]| -> Vec<String> {
+        keys.iter().map(|c| cell(row, *c)).collect()
+    };
+    let widen = |row: &Vec<String>| {
+        let mut row = row.clone();
+        row.resize(width, String::new());
+        row
+    };
+
+    let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for (i, row) in right.iter().enumerate().skip(start) {
+        index.entry(key_of(row, right_keys)).or_default().push(i);
+    }
+
+    let mut joined = Joined::default();
+    if has_header {
+        if let Some(header) = left.first() {
+            let mut header = widen(header);
+            header.extend(right.first().map(pick).unwrap_or_default());
+            joined.matrix.push(header);
+            joined.rows.push(Some(0));
+        }
+    }
+
+    let mut matched = vec![false; right.len()];
+    for (l, row) in left.iter().enumerate().skip(start) {
+        let key = key_of(row, left_keys);
+        match index.get(&key) {
+            Some(rows) => {
+                for r in rows {
+                    matched[*r] = true;
+                    let mut row = widen(row);
+                    row.extend(pick(&right[*r]));
+                    joined.matrix.push(row);
+                    joined.rows.push(Some(l));
+                }
+            }
+            None => {
+                joined.unmatched_left.push(show(&key));
+                if kind != JoinKind::Inner {
+                    let mut row = widen(row);
+                    row.resize(width + columns.len(), String::new());
+                    joined.matrix.push(row);
+                    joined.rows.push(Some(l));
+                }
+            }
+        }
+    }
+
+    for (r, row) in right.iter().enumerate().skip(start) {
+        if matched[r] {
+            continue;
+        }
+        let key = key_of(row, right_keys);
+        joined.unmatched_right.push(show(&key));
+        if kind == JoinKind::Outer {
+            let mut values = vec![String::new(); width];
+            for (c, value) in left_keys.iter().zip(key) {
+                if let Some(cell) = values.get_mut(*c) {
+                    *cell = value;
+                }
+            }
+            values.extend(pick(row));
+            joined.matrix.push(values);
+            joined.rows.push(None);
+        }
+    }
+
+    joined
+}
+
+/// The values of the key columns of a row, as they are reported.
+fn show(key: &[String]) -> String {
+    match key {
+        [value] => value.clone(),
+        values => values.join("/"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|r| r.iter().map(|v| v.to_string()).collect()).collect()
+    }
+
+    fn tables() -> (Vec<Vec<String>>, Vec<Vec<String>>) {
+        let left = matrix(&[&["id", "name"], &["1", "ann"], &["2", "bob"]]);
+        let right = matrix(&[
+            &["city", "id"],
+            &["Oslo", "1"],
+            &["Rome", "3"],
+            &["Bern", "1"],
+        ]);
+        (left, right)
+    }
+
+    #[test]
+    fn left_join() {
+        let (left, right) = tables();
+        let joined =
+            join(&left, &right, true, (&[0], &[1]), &[0], JoinKind::Left);
+
+        assert_eq!(
+            joined.matrix,
+            matrix(&[
+                &["id", "name", "city"],
+                &["1", "ann", "Oslo"],
+                &["1", "ann", "Bern"],
+                &["2", "bob", ""],
+            ])
+        );
+        assert_eq!(joined.rows, [Some(0), Some(1), Some(1), Some(2)]);
+        assert_eq!(joined.unmatched_left, ["2"]);
+        assert_eq!(joined.unmatched_right, ["3"]);
+    }
+
+    #[test]
+    fn inner_and_outer_joins() {
+        let (left, right) = tables();
+
+        let joined =
+            join(&left, &right, true, (&[0], &[1]), &[0], JoinKind::Inner);
+        assert_eq!(joined.matrix.len(), 3);
+        assert_eq!(joined.rows, [Some(0), Some(1), Some(1)]);
+
+        let joined =
+            join(&left, &right, true, (&[0], &[1]), &[0], JoinKind::Outer);
+        assert_eq!(joined.matrix[4], ["3", "", "Rome"]);
+        assert_eq!(joined.rows[4], None);
+    }
+
+    #[test]
+    fn many_keys_and_ragged_rows() {
+        let left = matrix(&[&["a", "x"], &["b"]]);
+        let right = matrix(&[&["a", "x", "1"], &["b", "y", "2"]]);
+        let joined = join(
+            &left,
+            &right,
+            false,
+            (&[0, 1], &[0, 1]),
+            &[2],
+            JoinKind::Outer,
+        );
+
+        assert_eq!(
+            joined.matrix,
+            matrix(&[&["a", "x", "1"], &["b", "", ""], &["b", "y", "2"]])
+        );
+        assert_eq!(joined.unmatched_left, ["b/"]);
+        assert_eq!(joined.unmatched_right, ["b/y"]);
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde_json::{Map, Value};
+
+/// How the values of a JSON file looked, to write them back the same way.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Shape {
+    /// Columns that only had strings, written back as strings even when a
+    /// value looks like a number. Values of the other columns are written
+    /// as the JSON they read as.
+    pub strings: HashSet<String>,
+    /// Columns with a `null` on some object. Empty cells of new rows in
+    /// other columns are left out of their object, as the key was missing.
+    pub nulls: HashSet<String>,
+    /// Columns with an empty string on some object, empty cells of new rows
+    /// in them are written as `""` when the column had no `null`.
+    pub empties: HashSet<String>,
+    /// The keys to each column on the objects, as a key may have a dot in it
+    /// and `a.b` is not always `{"a": {"b": ..}}`.
+    pub paths: HashMap<String, Vec<String>>,
+    /// The keys each object had, by the id of its row on the document.
+    pub rows: HashMap<usize, Fields>,
+}
+
+/// The columns an object had a key for, and which of them were `""`, so an
+/// empty cell is written back as it was read.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fields {
+    pub keys: HashSet<String>,
+    pub empties: HashSet<String>,
+    /// Strings that read as another JSON value, like `"123"`, by column.
+    /// They stay strings until the cell is changed.
+    pub strings: HashMap<String, String>,
+}
+
+/// Flatten an array of objects, or an object per line with `ndjson`, into a
+/// matrix with the keys on the header. The columns are every key found, in
+/// the order they are first seen. Nested objects become columns named by the
+/// path to them, like `address.city`, and arrays are kept as JSON. Each
+/// object is its row on the matrix, the first after the header having id 1.
+pub fn parse(text: &str, ndjson: bool) -> Result<(Vec<Vec<String>>, Shape)> {
+    let values: Vec<Value> = match ndjson {
+        true => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .wrap_err_with(|| format!("Invalid JSON on line {}", i + 1))
+            })
+            .collect::<Result<_>>()?,
+        false => match serde_json::from_str(text).wrap_err("Invalid JSON")? {
+            Value::Array(values) => values,
+            value => vec![value],
+        },
+    };
+
+    let mut shape = Shape::default();
+    let mut columns: Vec<String> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    // if each column had a null, and if it had only strings, when it had
+    // any other value
+    let mut nulls: Vec<bool> = Vec::new();
+    let mut strings: Vec<Option<bool>> = Vec::new();
+    let mut rows: Vec<Vec<(usize, String)>> = Vec::new();
+    for (i, value) in values.into_iter().enumerate() {
+        let Value::Object(object) = value else {
+            return Err(eyre!("Item {} is not an object", i + 1));
+        };
+        let mut paths = Vec::new();
+        flatten(&[], object, &mut paths);
+        let mut fields = Fields::default();
+        let mut row = Vec::new();
+        for (path, value) in paths {
+            let key = path.join(".");
+            let col = *index.entry(key.clone()).or_insert_with(|| {
+                columns.push(key.clone());
+                nulls.push(false);
+                strings.push(None);
+                shape.paths.insert(key.clone(), path);
+                columns.len() - 1
+            });
+            match &value {
+                Value::Null => nulls[col] = true,
+                Value::String(s) => {
+                    strings[col].get_or_insert(true);
+                    if s.is_empty() {
+                        shape.empties.insert(key.clone());
+                        fields.empties.insert(key.clone());
+                    } else if !matches!(read(s), Value::String(_)) {
+                        fields.strings.insert(key.clone(), s.clone());
+                    }
+                }
+                _ => strings[col] = Some(false),
+            }
+            fields.keys.insert(key);
+            row.push((col, cell(&value)));
+        }
+        shape.rows.insert(i + 1, fields);
+        rows.push(row);
+    }
+
+    for (col, column) in columns.iter().enumerate() {
+        if nulls[col] {
+            shape.nulls.insert(column.clone());
+        }
+        if strings[col] == Some(true) {
+            shape.strings.insert(column.clone());
+        }
+    }
+
+    let mut matrix = vec![columns.clone()];
+    for cells in rows {
+        let mut row = vec![String::new(); columns.len()];
+        for (col, cell) in cells {
+            row[col] = cell;
+        }
+        matrix.push(row);
+    }
+    Ok((matrix, shape))
+}
+
+/// Write rows back as an array of objects, or an object per line with
+/// `ndjson`, nesting the columns by the keys they were read from, or by the
+/// dots in their names when new. A row read from the file, by its id in
+/// `ids`, gets back the keys it had, with `""` or `null` on its empty cells
+/// as they were.
+pub fn to_string(
+    columns: &[String],
+    rows: &[Vec<String>],
+    ids: &[usize],
+    shape: &Shape,
+    ndjson: bool,
+) -> Result<String> {
+    let objects: Vec<Value> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let fields = ids.get(i).and_then(|id| shape.rows.get(id));
+            let mut object = Map::new();
+            for (col, name) in columns.iter().enumerate() {
+                let raw = row.get(col).map_or("", |v| v.as_str());
+                let value = match (raw, fields) {
+                    ("", Some(fields)) if fields.empties.contains(name) => {
+                        Value::from("")
+                    }
+                    ("", Some(fields)) if fields.keys.contains(name) => {
+                        empty(name, shape).unwrap_or(Value::Null)
+                    }
+                    ("", _) => match empty(name, shape) {
+                        Some(value) if fields.is_none() => value,
+                        _ => continue,
+                    },
+                    (raw, Some(fields))
+                        if fields
+                            .strings
+                            .get(name)
+                            .is_some_and(|s| s == raw) =>
+                    {
+                        Value::from(raw)
+                    }
+                    (raw, _) => value(name, raw, shape),
+                };
+                match shape.paths.get(name) {
+                    Some(path) => insert(&mut object, path, value),
+                    None => {
+                        let path: Vec<String> =
+                            name.split('.').map(String::from).collect();
+                        insert(&mut object, &path, value)
+                    }
+                }
+            }
+            Value::Object(object)
+        })
+        .collect();
+
+    match ndjson {
+        true => objects
+            .iter()
+            .map(|o| serde_json::to_string(o).map(|o| o + "\n"))
+            .collect::<Result<_, _>>()
+            .wrap_err("Could not write JSON"),
+        false => serde_json::to_string_pretty(&objects)
+            .map(|text| text + "\n")
+            .wrap_err("Could not write JSON"),
+    }
+}
+
+fn flatten(
+    prefix: &[String],
+    object: Map<String, Value>,
+    out: &mut Vec<(Vec<String>, Value)>,
+) {
+    for (key, value) in object {
+        let mut path = prefix.to_vec();
+        path.push(key);
+        match value {
+            Value::Object(object) if !object.is_empty() => {
+                flatten(&path, object, out)
+            }
+            value => out.push((path, value)),
+        }
+    }
+}
+
+/// What an empty cell of a column is written as when its row had no say,
+/// `null` when the column had one, `""` when it had empty strings, and
+/// nothing otherwise.
+fn empty(column: &str, shape: &Shape) -> Option<Value> {
+    if shape.nulls.contains(column) {
+        Some(Value::Null)
+    } else if shape.empties.contains(column) {
+        Some(Value::from(""))
+    } else {
+        None
+    }
+}
+
+/// What a cell shows for a value, strings without quotes and nothing for
+/// null.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// The JSON value of a cell, empty cells are null. Cells of columns that
+/// were strings stay strings, others are numbers, booleans, arrays or
+/// objects when they can be read as one.
+fn value(column: &str, raw: &str, shape: &Shape) -> Value {
+    if raw.is_empty() {
+        return Value::Null;
+    }
+    if shape.strings.contains(column) {
+        return Value::from(raw);
+    }
+    read(raw)
+}
+
+/// A cell as the JSON value it reads as, or as a string.
+fn read(raw: &str) -> Value {
+    match serde_json::from_str::<Value>(raw) {
+        Ok(Value::String(_)) | Err(_) => Value::from(raw),
+        Ok(value) => value,
+    }
+}
+
+/// Insert a value by its keys, creating the objects on the way. Keys that
+/// run into a value that is not an object are joined by dots into one.
+fn insert(object: &mut Map<String, Value>, path: &[String], value: Value) {
+    let [first, rest @ ..] = path else {
+        return;
+    };
+    if rest.is_empty() {
+        object.insert(first.clone(), value);
+        return;
+    }
+    let inner = object
+        .entry(first.clone())
+        .or_insert_with(|| Value::Object(Map::new()));
+    match inner {
+        Value::Object(inner) => insert(inner, rest, value),
+        _ => {
+            object.insert(path.join("."), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(text: &str, ndjson: bool) {
+        let (matrix, shape) = parse(text, ndjson).unwrap();
+        let ids: Vec<usize> = (1..matrix.len()).collect();
+        let written =
+            to_string(&matrix[0], &matrix[1..], &ids, &shape, ndjson).unwrap();
+        let read = |text: &str| -> Vec<Value> {
+            match ndjson {
+                true => text
+                    .lines()
+                    .map(|line| serde_json::from_str(line).unwrap())
+                    .collect(),
+                false => serde_json::from_str(text).unwrap(),
+            }
+        };
+        assert_eq!(read(&written), read(text));
+    }
+
+    #[test]
+    fn array_round_trip() {
+        round_trip(
+            r#"[
+                {"a.b": 1, "n": "", "v": 1, "x": null, "s": "007"},
+                {"c": {"d": true, "e": [1, 2]}, "n": "x", "x": 2},
+                {"c": {"d": false}, "v": 2.5, "x": "123", "s": "true"},
+                {}
+            ]"#,
+            false,
+        );
+    }
+
+    #[test]
+    fn ndjson_round_trip() {
+        round_trip("{\"n\":\"\",\"v\":1}\n{\"v\":2}\n", true);
+    }
+
+    #[test]
+    fn changed_cells_are_typed_again() {
+        let (mut matrix, shape) =
+            parse(r#"[{"m": 1}, {"m": "2"}, {"m": "x"}]"#, false).unwrap();
+        matrix[2][0] = "3".into();
+        matrix[3][0] = "4".into();
+        let written =
+            to_string(&matrix[0], &matrix[1..], &[1, 2, 3], &shape, true)
+                .unwrap();
+        assert_eq!(written, "{\"m\":1}\n{\"m\":3}\n{\"m\":4}\n");
+    }
+
+    #[test]
+    fn new_rows_follow_columns() {
+        let (matrix, shape) =
+            parse(r#"[{"n": "", "x": null}]"#, false).unwrap();
+        let rows = vec![vec![String::new(), String::new()]];
+        let written = to_string(&matrix[0], &rows, &[], &shape, true).unwrap();
+        assert_eq!(written, "{\"n\":\"\",\"x\":null}\n");
+    }
+}
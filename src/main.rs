@@ -1,19 +1,63 @@
-#![feature(if_let_guard)]
 mod action;
-mod utils;
 mod app;
+mod batch;
+mod cli;
+mod codec;
+mod columnar;
+mod command;
+mod completion;
 mod components;
+mod csv;
+mod dedupe;
+mod diff;
+mod document;
+mod export;
+mod file;
+mod fixed;
+mod formula;
+mod join;
+mod json;
 mod pages;
+mod pipe;
+mod pivot;
+mod schema;
+mod sql;
+mod sqlite;
+mod stats;
 mod tui;
+mod types;
+mod utils;
+mod xlsx;
 
 use app::App;
+use clap::Parser;
+use cli::{Args, Commands};
 use color_eyre::eyre::Result;
+use schema::Schema;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let mut app = App::new();
+    let args = Args::parse();
+    let schema = args.schema.as_deref().map(Schema::load).transpose()?;
+
+    let mut app = match args.command {
+        Some(Commands::Diff { left, right, key }) => {
+            let mut app = App::new(schema, args.files)?;
+            app.diff(left, right, key.as_deref())?;
+            app
+        }
+        Some(command) => {
+            std::process::exit(batch::run(command, schema)?.into())
+        }
+        None => App::new(schema, args.files)?,
+    };
     let result = app.run().await;
     result?;
 
+    // the table read from stdin goes to stdout, once the terminal is back
+    if let Some(table) = app.stdout() {
+        print!("{table}");
+    }
+
     Ok(())
 }
@@ -0,0 +1,187 @@
+use crossterm::event::KeyCode;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::{action::Action, tui::Event};
+
+use super::Page;
+
+/// Preview of the groups of duplicate rows, to pick which row of each group
+/// is kept.
+pub struct Dedupe {
+    pub layout: Layout,
+    /// Rows of the table, to show the values of the duplicates.
+    rows: Vec<Vec<String>>,
+    /// Indexes of the rows of each group.
+    groups: Vec<Vec<usize>>,
+    /// Which row of each group is kept, an index on the group.
+    keep: Vec<usize>,
+    /// Highlighted row, counting the rows of every group.
+    selected: usize,
+    /// The rows to remove, set when the user applies the choices.
+    pub applied: Option<Vec<usize>>,
+    /// If the page should be closed, going back to the table.
+    pub closed: bool,
+}
+
+impl Dedupe {
+    pub fn new(rows: Vec<Vec<String>>, groups: Vec<Vec<usize>>) -> Self {
+        Self {
+            layout: Layout::new(
+                Direction::Vertical,
+                [
+                    Constraint::Min(0),    // groups
+                    Constraint::Length(2), // keybindings help
+                ],
+            ),
+            keep: vec![0; groups.len()],
+            rows,
+            groups,
+            selected: 0,
+            applied: None,
+            closed: false,
+        }
+    }
+
+    /// The group and the index on the group of the highlighted row.
+    fn selected_row(&self) -> Option<(usize, usize)> {
+        let mut i = self.selected;
+        for (g, group) in self.groups.iter().enumerate() {
+            if i < group.len() {
+                return Some((g, i));
+            }
+            i -= group.len();
+        }
+        None
+    }
+
+    fn row_count(&self) -> usize {
+        self.groups.iter().map(|g| g.len()).sum()
+    }
+
+    /// Every row of every group that is not kept.
+    fn removed(&self) -> Vec<usize> {
+        self.groups
+            .iter()
+            .zip(&self.keep)
+            .flat_map(|(group, keep)| {
+                group.iter().enumerate().filter(move |(i, _)| i != keep)
+            })
+            .map(|(_, row)| *row)
+            .collect()
+    }
+}
+
+impl Page for Dedupe {
+    fn draw(&mut self, f: &mut Frame) {
+        let areas = self.layout.split(f.size());
+
+        let mut items = Vec::new();
+        for (g, (group, keep)) in self.groups.iter().zip(&self.keep).enumerate()
+        {
+            for (i, row) in group.iter().enumerate() {
+                let values = self.rows.get(*row).map_or(String::new(), |r| {
+                    r.iter()
+                        .map(|v| format!("{v:?}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                });
+                let mark = match i == *keep {
+                    true => "keep  ".green(),
+                    false => "remove".red(),
+                };
+                let row = Line::from(vec![
+                    mark,
+                    format!(" {:>5} {values}", row + 1).into(),
+                ]);
+
+                items.push(ListItem::new(match i {
+                    0 => vec![
+                        Line::from(
+                            format!("Group {}, {} rows", g + 1, group.len())
+                                .bold(),
+                        ),
+                        row,
+                    ],
+                    _ => vec![row],
+                }));
+            }
+        }
+
+        let removed = self.row_count() - self.groups.len();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(format!(
+                        "{} groups of duplicates, {removed} rows to remove",
+                        self.groups.len()
+                    ))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded),
+            )
+            .highlight_style(Style::default().reversed());
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        f.render_stateful_widget(list, areas[0], &mut state);
+
+        let text = Line::from(vec![
+            "move: ".into(),
+            "jk".bold().blue(),
+            " keep this: ".into(),
+            "Space".bold().blue(),
+            " keep first: ".into(),
+            "f".bold().blue(),
+            " keep last: ".into(),
+            "L".bold().blue(),
+            " apply: ".into(),
+            "Enter".bold().blue(),
+            " cancel: ".into(),
+            "q".bold().blue(),
+        ]);
+        f.render_widget(Paragraph::new(text), areas[1]);
+    }
+
+    fn get_action(&self, event: Event) -> Action {
+        match event {
+            Event::Error => Action::None,
+            Event::Tick => Action::Tick,
+            Event::Render => Action::Render,
+            Event::Key(key) => Action::Key(key),
+            Event::Resize(w, h) => Action::Resize(w, h),
+            Event::Quit => Action::Quit,
+            _ => Action::None,
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        let Action::Key(key) = action else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => self.focus_next(),
+            KeyCode::Char('k') | KeyCode::Up => self.focus_prev(),
+            KeyCode::Char(' ') => {
+                if let Some((group, i)) = self.selected_row() {
+                    self.keep[group] = i;
+                }
+            }
+            KeyCode::Char('f') => self.keep.fill(0),
+            KeyCode::Char('L') => {
+                for (keep, group) in self.keep.iter_mut().zip(&self.groups) {
+                    *keep = group.len() - 1;
+                }
+            }
+            KeyCode::Enter => self.applied = Some(self.removed()),
+            KeyCode::Char('q') | KeyCode::Esc => self.closed = true,
+            _ => {}
+        }
+    }
+
+    fn focus_next(&mut self) {
+        if self.selected + 1 < self.row_count() {
+            self.selected += 1;
+        }
+    }
+
+    fn focus_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
@@ -0,0 +1,443 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::{eyre, Result};
+use crossterm::event::KeyCode;
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc;
+
+use crate::{
+    action::Action,
+    components::{csv_table::CsvTable, Component, HandleActionResponse},
+    csv,
+    diff::{self, Pair},
+    document::Document,
+    tui::Event,
+    types,
+};
+
+use super::Page;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn other(&self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// Two tables side by side, with their rows lined up and the differences
+/// highlighted. Rows only on one side are padded with an empty row on the
+/// other side.
+pub struct Diff<'a> {
+    pub layout: Layout,
+    pub left: CsvTable<'a>,
+    pub right: CsvTable<'a>,
+    /// Names shown above each table.
+    pub left_name: String,
+    pub right_name: String,
+    /// Files each side is saved to with `w`.
+    pub left_path: Option<PathBuf>,
+    pub right_path: Option<PathBuf>,
+    /// Which rows of the padded tables are rows of each table.
+    present: Vec<(bool, bool)>,
+    focus: Side,
+    /// If values were copied to a side since it was saved.
+    pub left_changed: bool,
+    pub right_changed: bool,
+    /// Set after warning that closing loses the changes.
+    warned: bool,
+    /// Result of the last key, shown until the next key press.
+    message: Option<Line<'static>>,
+    /// If the page should be closed, going back to the table.
+    pub closed: bool,
+    /// Quit the app instead of closing, when the page is not opened from
+    /// another page.
+    pub quit_on_close: bool,
+    pub tx: mpsc::UnboundedSender<Action>,
+}
+
+impl<'a> Diff<'a> {
+    /// Line up the rows of the tables by the `key` column, a name on the
+    /// header or a letter, or by their position when None.
+    pub fn new(
+        tx: mpsc::UnboundedSender<Action>,
+        left: Vec<Vec<String>>,
+        right: Vec<Vec<String>>,
+        key: Option<&str>,
+    ) -> Result<Self> {
+        let has_header = types::has_header(&left);
+        let key = match key {
+            Some(key) => {
+                let column = |matrix: &Vec<Vec<String>>, side: &str| {
+                    Document::new(matrix.clone())
+                        .header(has_header)
+                        .column_by_name(key)
+                        .ok_or(eyre!(
+                            "Unknown column {key} on the {side} table"
+                        ))
+                };
+                Some((column(&left, "left")?, column(&right, "right")?))
+            }
+            None => None,
+        };
+
+        let pairs = diff::align(&left, &right, key, has_header);
+        let pad = |matrix: &[Vec<String>], row: Option<usize>| {
+            row.map_or(Vec::new(), |row| matrix[row].clone())
+        };
+        let table = |matrix: Vec<Vec<String>>| {
+            let document = Document::new(matrix).header(has_header).shared();
+            CsvTable::new(document).read_only(true)
+        };
+
+        let mut page = Self {
+            layout: Layout::new(
+                Direction::Vertical,
+                [
+                    Constraint::Min(0),    // tables
+                    Constraint::Length(2), // keybindings help
+                ],
+            ),
+            left: table(pairs.iter().map(|(l, _)| pad(&left, *l)).collect()),
+            right: table(pairs.iter().map(|(_, r)| pad(&right, *r)).collect()),
+            left_name: "left".into(),
+            right_name: "right".into(),
+            left_path: None,
+            right_path: None,
+            present: pairs
+                .iter()
+                .map(|(l, r): &Pair| (l.is_some(), r.is_some()))
+                .collect(),
+            focus: Side::Left,
+            left_changed: false,
+            right_changed: false,
+            warned: false,
+            message: None,
+            closed: false,
+            quit_on_close: false,
+            tx,
+        };
+        page.right.focused = false;
+        for row in 0..page.present.len() {
+            page.highlight(row);
+        }
+        Ok(page)
+    }
+
+    /// The name shown above a side and the file it is saved to.
+    pub fn path(mut self, side: Side, path: Option<PathBuf>) -> Self {
+        let name = path.as_ref().map(|p| p.display().to_string());
+        match side {
+            Side::Left => {
+                self.left_name = name.unwrap_or(self.left_name);
+                self.left_path = path;
+            }
+            Side::Right => {
+                self.right_name = name.unwrap_or(self.right_name);
+                self.right_path = path;
+            }
+        }
+        self
+    }
+
+    /// Name a side that is not saved to a file.
+    pub fn name(mut self, side: Side, name: &str) -> Self {
+        match side {
+            Side::Left => self.left_name = name.to_string(),
+            Side::Right => self.right_name = name.to_string(),
+        }
+        self
+    }
+
+    pub fn quit_on_close(mut self, quit_on_close: bool) -> Self {
+        self.quit_on_close = quit_on_close;
+        self
+    }
+
+    fn table(&mut self, side: Side) -> &mut CsvTable<'a> {
+        match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+        }
+    }
+
+    fn is_present(&self, row: usize, side: Side) -> bool {
+        self.present.get(row).is_some_and(|(l, r)| match side {
+            Side::Left => *l,
+            Side::Right => *r,
+        })
+    }
+
+    /// The rows of a side, without the padding.
+    pub fn matrix(&self, side: Side) -> Vec<Vec<String>> {
+        let table = match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        };
+        table
+            .doc()
+            .matrix
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| self.is_present(*row, side))
+            .map(|(_, values)| values.clone())
+            .collect()
+    }
+
+    /// Columns that differ on a row, every column of the side that has the
+    /// row when it is only on one side.
+    fn differences(&self, row: usize) -> Vec<usize> {
+        let (left, right) = (self.left.doc(), self.right.doc());
+        let left = left.matrix.get(row).map_or(&[][..], |r| r);
+        let right = right.matrix.get(row).map_or(&[][..], |r| r);
+        match self.present.get(row) {
+            Some((true, true)) => diff::changed_cells(left, right),
+            Some((true, false)) => (0..left.len()).collect(),
+            Some((false, true)) => (0..right.len()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Mark the differences of a row, removed rows are red, added rows are
+    /// green and changed cells are yellow.
+    fn highlight(&mut self, row: usize) {
+        let color = match self.present.get(row) {
+            Some((true, false)) => Color::Red,
+            Some((false, true)) => Color::Green,
+            _ => Color::Yellow,
+        };
+        let cells = self.differences(row);
+        for table in [&mut self.left, &mut self.right] {
+            table.highlights.retain(|(r, _), _| *r != row);
+            for col in &cells {
+                table.highlights.insert((row, *col), color);
+            }
+        }
+    }
+
+    /// Focus the next row that differs, or the previous one.
+    fn next_difference(&mut self, forward: bool) {
+        let row = self.left.cell_focused.0;
+        let next = match forward {
+            true => (row + 1..self.present.len())
+                .find(|r| !self.differences(*r).is_empty()),
+            false => (0..row).rev().find(|r| !self.differences(*r).is_empty()),
+        };
+        match next {
+            Some(next) => {
+                self.left.cell_focused = (next, 0);
+                self.right.cell_focused = (next, 0);
+            }
+            None => self.message = Some(Line::from("No more differences")),
+        }
+    }
+
+    /// Copy the focused cell from a side to the other one, or the whole row
+    /// when the other side does not have it.
+    fn copy(&mut self, from: Side) {
+        let (row, col) = self.table(from).cell_focused;
+        let to = from.other();
+        if !self.is_present(row, from) {
+            self.message = Some(Line::from("Nothing to copy".red()));
+            return;
+        }
+
+        let source = self.table(from).doc().matrix[row].clone();
+        let values = match self.is_present(row, to) {
+            true => {
+                let mut values = self.table(to).doc().matrix[row].clone();
+                if values.len() <= col {
+                    values.resize(col + 1, String::new());
+                }
+                values[col] = source.get(col).cloned().unwrap_or_default();
+                values
+            }
+            false => source,
+        };
+        self.table(to).doc_mut().replace_row(row, values);
+
+        match to {
+            Side::Left => {
+                self.present[row].0 = true;
+                self.left_changed = true;
+            }
+            Side::Right => {
+                self.present[row].1 = true;
+                self.right_changed = true;
+            }
+        }
+        self.warned = false;
+        self.highlight(row);
+    }
+
+    /// Write the sides that changed and have a file.
+    fn save(&mut self) -> Result<()> {
+        let mut saved = Vec::new();
+        if let (true, Some(path)) = (self.left_changed, &self.left_path) {
+            csv::save(path, &self.matrix(Side::Left))?;
+            self.left_changed = false;
+            saved.push(path.display().to_string());
+        }
+        if let (true, Some(path)) = (self.right_changed, &self.right_path) {
+            csv::save(path, &self.matrix(Side::Right))?;
+            self.right_changed = false;
+            saved.push(path.display().to_string());
+        }
+        self.message = Some(Line::from(match saved.is_empty() {
+            true => "Nothing to save".to_string(),
+            false => format!("Saved {}", saved.join(", ")),
+        }));
+        Ok(())
+    }
+
+    /// Changes that are lost when the page is closed.
+    fn unsaved(&self) -> bool {
+        (self.left_changed && self.left_path.is_some())
+            || (self.right_changed && self.right_path.is_some())
+    }
+
+    fn summary(&self) -> String {
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+        for (row, present) in self.present.iter().enumerate() {
+            match present {
+                (true, false) => removed += 1,
+                (false, true) => added += 1,
+                _ if !self.differences(row).is_empty() => changed += 1,
+                _ => {}
+            }
+        }
+        format!("{added} added, {removed} removed, {changed} changed rows")
+    }
+
+    fn draw_side(&mut self, f: &mut Frame, rect: Rect, side: Side) {
+        let [title, table] = *Layout::new(
+            Direction::Vertical,
+            [Constraint::Length(1), Constraint::Min(0)],
+        )
+        .split(rect) else {
+            return;
+        };
+        let name = match side {
+            Side::Left => &self.left_name,
+            Side::Right => &self.right_name,
+        };
+        let mut name = Span::from(name.clone());
+        if self.focus == side {
+            name = name.bold().underlined();
+        }
+        f.render_widget(Paragraph::new(name), title);
+        self.table(side).draw(f, table);
+    }
+}
+
+impl<'a> Page for Diff<'a> {
+    fn draw(&mut self, f: &mut Frame) {
+        let areas = self.layout.split(f.size());
+        let [left, right] = *Layout::new(
+            Direction::Horizontal,
+            [Constraint::Percentage(50), Constraint::Percentage(50)],
+        )
+        .split(areas[0]) else {
+            return;
+        };
+        self.draw_side(f, left, Side::Left);
+        let right = Rect {
+            x: right.x + 1,
+            width: right.width.saturating_sub(1),
+            ..right
+        };
+        self.draw_side(f, right, Side::Right);
+
+        let text = vec![
+            Line::from(vec![
+                "move: ".into(),
+                "hjkl".bold().blue(),
+                " next/prev change: ".into(),
+                "n N".bold().blue(),
+                " copy: ".into(),
+                "> <".bold().blue(),
+                " switch: ".into(),
+                "Tab".bold().blue(),
+                " save: ".into(),
+                "w".bold().blue(),
+                " back: ".into(),
+                "q".bold().blue(),
+            ]),
+            self.message.clone().unwrap_or_else(|| Line::from(self.summary())),
+        ];
+        f.render_widget(Paragraph::new(text), areas[1]);
+    }
+
+    fn get_action(&self, event: Event) -> Action {
+        match event {
+            Event::Error => Action::None,
+            Event::Tick => Action::Tick,
+            Event::Render => Action::Render,
+            Event::Key(key) => Action::Key(key),
+            Event::Resize(w, h) => Action::Resize(w, h),
+            Event::Quit => Action::Quit,
+            _ => Action::None,
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        let Action::Key(key) = action else {
+            return;
+        };
+        self.message = None;
+
+        let side = self.focus;
+        if let HandleActionResponse::Ignore =
+            self.table(side).handle_action(action)
+        {
+            return;
+        }
+        // both tables move together
+        let focused = self.table(side).cell_focused;
+        self.table(side.other()).cell_focused = focused;
+
+        match key.code {
+            KeyCode::Char('n') => self.next_difference(true),
+            KeyCode::Char('N') => self.next_difference(false),
+            KeyCode::Char('>') => self.copy(Side::Left),
+            KeyCode::Char('<') => self.copy(Side::Right),
+            KeyCode::Tab => self.focus_next(),
+            KeyCode::BackTab => self.focus_prev(),
+            KeyCode::Char('w') => {
+                if let Err(e) = self.save() {
+                    self.message = Some(Line::from(e.to_string().red()));
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if self.unsaved() && !self.warned {
+                    self.warned = true;
+                    self.message = Some(Line::from(
+                        "Changes not saved, save: w, discard: q".red(),
+                    ));
+                } else if self.quit_on_close {
+                    self.tx.send(Action::Quit).unwrap();
+                } else {
+                    self.closed = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn focus_next(&mut self) {
+        self.focus = self.focus.other();
+        self.left.focused = self.focus == Side::Left;
+        self.right.focused = self.focus == Side::Right;
+    }
+
+    fn focus_prev(&mut self) {
+        self.focus_next();
+    }
+}
@@ -1,58 +1,813 @@
-use crossterm::event::KeyCode;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crossterm::event::{KeyCode, MouseEventKind};
 use ratatui::{prelude::*, widgets::*};
 use tokio::sync::mpsc;
 
+use color_eyre::eyre::{eyre, Result};
+
 use crate::{
     action::Action,
-    components::{csv_table::CsvTable, Component, HandleActionResponse},
+    command::Command,
+    components::{
+        csv_table::CsvTable,
+        input::{Input, Mode},
+        Component, HandleActionResponse,
+    },
+    csv, dedupe,
+    document::{Document, SharedDocument},
+    export,
+    file::FileFormat,
+    join, pipe,
+    schema::Schema,
     tui::Event,
+    utils::centered_rect,
+};
+
+use super::{
+    dedupe::Dedupe,
+    diff::{Diff, Side},
+    pivot::Pivot,
+    query::Query,
+    Page,
 };
 
-use super::Page;
+/// Values yanked from a buffer, to be pasted on any buffer.
+#[derive(Clone, Debug)]
+pub enum Register {
+    Cell(String),
+    Row(Vec<String>),
+    /// Cells selected with the mouse, row by row.
+    Range(Vec<Vec<String>>),
+}
+
+/// The sheets of the workbook, or the tables of the database, of the focused
+/// table, to pick the one that is shown.
+pub struct SheetPicker {
+    pub title: &'static str,
+    pub sheets: Vec<String>,
+    pub selected: usize,
+}
+
+/// A tab of the tab bar, with one or more views of tables side by side or one
+/// above the other.
+pub struct Buffer<'a> {
+    /// Each view has its own cursor and scroll, views of the same file share
+    /// the document.
+    pub views: Vec<CsvTable<'a>>,
+    /// The view that gets the keys.
+    pub focused: usize,
+    /// How the views are split, `Horizontal` puts them side by side.
+    pub direction: Direction,
+}
+
+impl<'a> Buffer<'a> {
+    pub fn new(view: CsvTable<'a>) -> Self {
+        Self { views: vec![view], focused: 0, direction: Direction::Vertical }
+    }
+
+    pub fn view(&self) -> &CsvTable<'a> {
+        &self.views[self.focused]
+    }
+
+    pub fn view_mut(&mut self) -> &mut CsvTable<'a> {
+        &mut self.views[self.focused]
+    }
+
+    /// Add a view after the focused one and focus it.
+    fn split(&mut self, view: CsvTable<'a>, direction: Direction) {
+        self.direction = direction;
+        self.views.insert(self.focused + 1, view);
+        self.focus(self.focused + 1);
+    }
+
+    fn focus(&mut self, view: usize) {
+        self.focused = view;
+        for (i, view) in self.views.iter_mut().enumerate() {
+            view.focused = i == self.focused;
+        }
+    }
+
+    /// Focus the next view, or the previous one, wrapping around.
+    fn cycle(&mut self, forward: bool) {
+        let len = self.views.len();
+        self.focus(match forward {
+            true => (self.focused + 1) % len,
+            false => (self.focused + len - 1) % len,
+        });
+    }
+
+    /// Close the focused view, refused when it is the only one, or when it
+    /// is the last view of a table with changes not saved unless `force`.
+    fn close(&mut self, force: bool) -> Result<()> {
+        if self.views.len() == 1 {
+            return Err(eyre!("Cannot close the only view, close it with bd"));
+        }
+        if !force {
+            self.check_close(|i| i == self.focused, "close!")?;
+        }
+        self.views.remove(self.focused);
+        self.focus(self.focused.min(self.views.len() - 1));
+        Ok(())
+    }
+
+    /// Close every view but the focused one, refused the same way.
+    fn only(&mut self, force: bool) -> Result<()> {
+        if !force {
+            self.check_close(|i| i != self.focused, "only!")?;
+        }
+        let view = self.views.swap_remove(self.focused);
+        self.views = vec![view];
+        self.focus(0);
+        Ok(())
+    }
+
+    /// Refuse to close the views picked by `closed` when they are the last
+    /// views of a table with changes not saved.
+    fn check_close(
+        &self,
+        closed: impl Fn(usize) -> bool,
+        retry: &str,
+    ) -> Result<()> {
+        let closed: Vec<&CsvTable> = (0..self.views.len())
+            .filter(|i| closed(*i))
+            .map(|i| &self.views[i])
+            .collect();
+        for view in &closed {
+            let views = closed
+                .iter()
+                .filter(|v| Rc::ptr_eq(&v.document, &view.document))
+                .count();
+            let doc = view.doc();
+            if Rc::strong_count(&view.document) == views && doc.dirty {
+                return Err(eyre!(
+                    "{} has changes not saved, close it anyway with {retry}",
+                    doc.name()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, f: &mut Frame, rect: Rect) {
+        if self.views.len() == 1 {
+            self.views[0].draw(f, rect);
+            return;
+        }
+
+        let count = self.views.len() as u32;
+        let areas = Layout::new(
+            self.direction,
+            self.views.iter().map(|_| Constraint::Ratio(1, count)),
+        )
+        .split(rect);
+
+        for (i, (view, area)) in
+            self.views.iter_mut().zip(areas.iter()).enumerate()
+        {
+            let style = match i == self.focused {
+                true => Style::new().blue(),
+                false => Style::new().dark_gray(),
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(style)
+                .title(view.doc().name());
+            let inner = block.inner(*area);
+            f.render_widget(block, *area);
+            view.draw(f, inner);
+        }
+    }
+}
 
 pub struct Home<'a> {
     pub layout: Layout,
-    pub csv_table: CsvTable<'a>,
-    /// The component from components which is being focused
-    pub focused: usize,
+    /// A tab for each open file, with its views.
+    pub buffers: Vec<Buffer<'a>>,
+    /// The buffer that is shown.
+    pub current: usize,
+    /// What `y` and `Y` yanked, pasted with `p`.
+    pub register: Option<Register>,
+    /// Contract that the files opened are validated against.
+    pub schema: Option<Schema>,
+    /// The command line, opened with `:`.
+    pub command_input: Option<Input<'a>>,
+    /// Result of the last command, shown until the next key press.
+    pub message: Option<Line<'static>>,
+    /// The result of an SQL query, shown instead of the table when Some.
+    pub query: Option<Query<'a>>,
+    /// The pivot table of the table, shown instead of it when Some.
+    pub pivot: Option<Pivot<'a>>,
+    /// The groups of duplicate rows, shown instead of the table when Some.
+    pub dedupe: Option<Dedupe>,
+    /// The table compared with a file, shown instead of the table when Some.
+    pub diff: Option<Diff<'a>>,
+    /// The sheets of a workbook, shown on a popup over the table when Some.
+    pub sheets: Option<SheetPicker>,
     pub tx: mpsc::UnboundedSender<Action>,
 }
 
 impl<'a> Home<'a> {
-    pub fn new(tx: mpsc::UnboundedSender<Action>) -> Self {
-        Self {
+    /// Open the `files` on a buffer each, or the sample table when there
+    /// are none. A file named `-` is the table read from `stdin`.
+    pub fn new(
+        tx: mpsc::UnboundedSender<Action>,
+        schema: Option<Schema>,
+        files: &[PathBuf],
+        stdin: Option<SharedDocument>,
+    ) -> Result<Self> {
+        let mut home = Self {
             layout: Layout::new(
                 Direction::Vertical,
                 [
-                    Constraint::Length(50), // cells
-                    Constraint::Length(2),  // keybindings help
-                    Constraint::Min(0),     // fills remaining space
+                    Constraint::Length(1), // buffers
+                    Constraint::Min(0),    // cells, the rest of the terminal
+                    Constraint::Length(2), // keybindings help
                 ],
             ),
-            csv_table: CsvTable::new(tx.clone()),
-            focused: 0,
+            buffers: Vec::new(),
+            current: 0,
+            register: None,
+            schema,
+            command_input: None,
+            message: None,
+            query: None,
+            pivot: None,
+            dedupe: None,
+            diff: None,
+            sheets: None,
             tx,
+        };
+
+        for path in files {
+            match (pipe::is_stdin(path), &stdin) {
+                (true, Some(document)) => home.push_shared(document.clone()),
+                (true, None) => {}
+                (false, _) => home.open(path)?,
+            }
+        }
+        if home.buffers.is_empty() {
+            let matrix = csv::parse(include_str!("../../testdata.csv"));
+            let document = Document::new(matrix)
+                .path(Some("testdata.csv".into()))
+                .schema(home.schema.clone());
+            home.push(document);
+        }
+        home.current = 0;
+        Ok(home)
+    }
+
+    /// The focused view of the buffer that is shown.
+    pub fn table(&self) -> &CsvTable<'a> {
+        self.buffers[self.current].view()
+    }
+
+    pub fn table_mut(&mut self) -> &mut CsvTable<'a> {
+        self.buffers[self.current].view_mut()
+    }
+
+    /// Show a document on a new buffer.
+    fn push(&mut self, document: Document) {
+        self.push_shared(document.shared());
+    }
+
+    fn push_shared(&mut self, document: SharedDocument) {
+        let table = CsvTable::new(document);
+        self.buffers.push(Buffer::new(table));
+        self.current = self.buffers.len() - 1;
+    }
+
+    /// The buffer and view that show a file.
+    fn find(&self, path: &Path) -> Option<(usize, usize)> {
+        self.buffers.iter().enumerate().find_map(|(b, buffer)| {
+            let view = buffer
+                .views
+                .iter()
+                .position(|v| v.doc().path.as_deref() == Some(path))?;
+            Some((b, view))
+        })
+    }
+
+    /// Open a file on a new buffer, or go to its buffer if it is already
+    /// open.
+    fn open(&mut self, path: &Path) -> Result<()> {
+        if let Some((buffer, view)) = self.find(path) {
+            self.current = buffer;
+            self.buffers[buffer].focus(view);
+            return Ok(());
+        }
+        let document = Document::open(path, self.schema.clone())?;
+        let others = document.format.sheets().is_some_and(|(_, s)| s.len() > 1);
+        self.push(document);
+        // the first sheet is shown, and the others are offered
+        if others {
+            self.sheets = Some(self.sheet_picker()?);
+        }
+        Ok(())
+    }
+
+    /// A picker of the sheets of the workbook, or the tables of the
+    /// database, of the focused table.
+    fn sheet_picker(&self) -> Result<SheetPicker> {
+        let doc = self.table().doc();
+        let (current, sheets) = doc
+            .format
+            .sheets()
+            .ok_or(eyre!("{} has no sheets or tables", doc.name()))?;
+        Ok(SheetPicker {
+            title: match doc.format {
+                FileFormat::Sqlite(_) => "Tables, open: Enter",
+                _ => "Sheets, open: Enter",
+            },
+            selected: sheets.iter().position(|s| s == current).unwrap_or(0),
+            sheets: sheets.to_vec(),
+        })
+    }
+
+    /// Show another sheet or table of the file of the focused table, on every
+    /// view of it. Refused when the table has changes not saved, they would
+    /// be lost.
+    fn open_sheet(&mut self, sheet: &str) -> Result<()> {
+        let document = self.table().document.clone();
+        let doc = document.borrow();
+        if doc.dirty {
+            return Err(eyre!(
+                "{} has changes not saved, save them with :w <file>",
+                doc.name()
+            ));
+        }
+        let path = doc.path.clone().ok_or(eyre!("No file to read from"))?;
+        drop(doc);
+
+        *document.borrow_mut() =
+            Document::open_sheet(&path, sheet)?.schema(self.schema.clone());
+        for view in self.buffers.iter_mut().flat_map(|b| b.views.iter_mut()) {
+            if Rc::ptr_eq(&view.document, &document) {
+                view.cell_focused = (0, 0);
+                view.selection = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_sheets(&self, f: &mut Frame, rect: Rect, picker: &SheetPicker) {
+        let area = centered_rect(40, 50, rect);
+        f.render_widget(Clear, area);
+
+        let items: Vec<ListItem> =
+            picker.sheets.iter().map(|s| ListItem::new(s.as_str())).collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(picker.title)
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded),
+            )
+            .highlight_style(Style::default().reversed());
+        let mut state =
+            ListState::default().with_selected(Some(picker.selected));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Handle a key press while the sheets are shown.
+    fn handle_sheets_action(&mut self, action: Action) {
+        let (Some(picker), Action::Key(key)) = (&mut self.sheets, action)
+        else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                picker.selected =
+                    (picker.selected + 1).min(picker.sheets.len() - 1);
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                picker.selected = picker.selected.saturating_sub(1);
+            }
+            KeyCode::Enter => {
+                let sheet = picker.sheets[picker.selected].clone();
+                self.sheets = None;
+                if let Err(e) = self.open_sheet(&sheet) {
+                    self.message = Some(Line::from(e.to_string().red()));
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => self.sheets = None,
+            _ => {}
+        }
+    }
+
+    /// Split the buffer with a new view of the focused table, or of a file.
+    /// A file that is already open shares its document with the other views,
+    /// so changes show on all of them.
+    fn split(
+        &mut self,
+        path: Option<&Path>,
+        direction: Direction,
+    ) -> Result<()> {
+        let (document, cell): (SharedDocument, _) = match path {
+            None => (self.table().document.clone(), self.table().cell_focused),
+            Some(path) => match self.find(path) {
+                Some((b, v)) => {
+                    (self.buffers[b].views[v].document.clone(), (0, 0))
+                }
+                None => {
+                    let document = Document::open(path, self.schema.clone())?;
+                    (document.shared(), (0, 0))
+                }
+            },
+        };
+        let mut view = CsvTable::new(document);
+        view.cell_focused = cell;
+        self.buffers[self.current].split(view, direction);
+        Ok(())
+    }
+
+    /// Close the buffer that is shown, refused when it has changes not
+    /// saved unless `force`.
+    fn close_buffer(&mut self, force: bool) -> Result<()> {
+        let name = self.table().doc().name();
+        if self.buffers.len() == 1 {
+            return Err(eyre!("{name} is the only buffer, quit with q"));
+        }
+        let dirty =
+            self.buffers[self.current].views.iter().any(|v| v.doc().dirty);
+        if dirty && !force {
+            return Err(eyre!(
+                "{name} has changes not saved, close it anyway with bd!"
+            ));
+        }
+        self.buffers.remove(self.current);
+        self.current = self.current.min(self.buffers.len() - 1);
+        Ok(())
+    }
+
+    /// Quit, refused when a file has changes not saved unless `force`.
+    /// Tables without a file, like the one read from stdin, are left out.
+    fn quit(&mut self, force: bool) -> Result<()> {
+        let mut dirty: Vec<String> = Vec::new();
+        for view in self.buffers.iter().flat_map(|b| &b.views) {
+            let doc = view.doc();
+            if doc.dirty && doc.path.is_some() && !dirty.contains(&doc.name()) {
+                dirty.push(doc.name());
+            }
+        }
+        if !dirty.is_empty() && !force {
+            return Err(eyre!(
+                "Changes not saved on {}, quit anyway with q!",
+                dirty.join(", ")
+            ));
+        }
+        self.tx.send(Action::Quit).unwrap();
+        Ok(())
+    }
+
+    /// Go to the next buffer, or the previous one, wrapping around.
+    fn cycle_buffer(&mut self, forward: bool) {
+        let len = self.buffers.len();
+        self.current = match forward {
+            true => (self.current + 1) % len,
+            false => (self.current + len - 1) % len,
+        };
+    }
+
+    /// Yank the selected cells, the focused cell, or its whole row.
+    fn yank(&mut self, row: bool) {
+        let table = self.table();
+        let (r, _) = table.cell_focused;
+        let register = match (row, table.selected()) {
+            (true, _) => table.doc().matrix.get(r).cloned().map(Register::Row),
+            (false, Some(range)) => Some(Register::Range(range)),
+            (false, None) => table.get_focused_cell().map(Register::Cell),
+        };
+        self.message = match &register {
+            Some(Register::Range(range)) => Some(Line::from(format!(
+                "Yanked {} cells",
+                range.iter().map(|r| r.len()).sum::<usize>()
+            ))),
+            Some(Register::Row(_)) => Some(Line::from(format!(
+                "Yanked row {} of {}",
+                r + 1,
+                table.doc().name()
+            ))),
+            Some(Register::Cell(_)) => Some(Line::from("Yanked cell")),
+            None => return,
+        };
+        self.register = register;
+    }
+
+    /// Paste the register, a cell on the focused cell, a range from the
+    /// focused cell and a row below the focused row.
+    fn paste(&mut self) {
+        let Some(register) = self.register.clone() else {
+            self.message = Some(Line::from("Nothing to paste, yank: y Y"));
+            return;
+        };
+        if let Err(e) = self.check_editable() {
+            self.message = Some(Line::from(e.to_string().red()));
+            return;
+        }
+        let table = self.table_mut();
+        let (row, _) = table.cell_focused;
+        match register {
+            Register::Cell(value) => {
+                table.doc_mut().set_cell(table.cell_focused, value);
+            }
+            Register::Range(values) => {
+                table.doc_mut().set_range(table.cell_focused, &values);
+            }
+            Register::Row(values) => {
+                let at = (row + 1).min(table.doc().matrix.len());
+                table.doc_mut().insert_row(at, values);
+                table.cell_focused.0 = at;
+            }
+        }
+    }
+
+    /// Refuse to change the table when it is read only, telling why.
+    fn check_editable(&self) -> Result<()> {
+        match self.table().doc().read_only() {
+            Some(reason) => Err(eyre!(reason)),
+            None => Ok(()),
+        }
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<()> {
+        match command.parse()? {
+            Command::AddColumn { name, expr, live } => {
+                self.check_editable()?;
+                self.table().doc_mut().add_column(&name, &expr, live)?;
+            }
+            Command::Sql { query } => {
+                let result = self.table().doc().query(&query)?;
+                self.query = Some(Query::new(query, result));
+            }
+            Command::Pivot => {
+                let doc = self.table().doc();
+                let header = doc.column_names();
+                let body = doc.body().to_vec();
+                drop(doc);
+                self.pivot = Some(Pivot::new(header, body));
+            }
+            Command::Dedupe { keys, options } => {
+                self.check_editable()?;
+                let doc = self.table().doc();
+                let keys = keys
+                    .iter()
+                    .map(|k| {
+                        doc.column_by_name(k).ok_or(eyre!("Unknown column {k}"))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let matrix = doc.matrix.clone();
+                let start = if doc.has_header { 1 } else { 0 };
+                drop(doc);
+                let groups: Vec<Vec<usize>> = dedupe::groups(
+                    matrix.get(start..).unwrap_or_default(),
+                    &keys,
+                    options,
+                )
+                .into_iter()
+                .map(|g| g.into_iter().map(|row| row + start).collect())
+                .collect();
+
+                if groups.is_empty() {
+                    self.message = Some(Line::from("No duplicate rows"));
+                } else {
+                    self.dedupe = Some(Dedupe::new(matrix, groups));
+                }
+            }
+            Command::Join { path, kind, keys, other_keys, columns } => {
+                self.check_editable()?;
+                let document = self.table().document.clone();
+                let doc = document.borrow();
+                let other =
+                    Document::new(csv::load(&path)?).header(doc.has_header);
+                let name = path.display();
+
+                let left_keys = keys
+                    .iter()
+                    .map(|k| {
+                        doc.column_by_name(k).ok_or(eyre!("Unknown column {k}"))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let right_keys = other_keys
+                    .as_ref()
+                    .unwrap_or(&keys)
+                    .iter()
+                    .map(|k| {
+                        other
+                            .column_by_name(k)
+                            .ok_or(eyre!("Unknown column {k} on {name}"))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let columns: Vec<usize> = match columns.is_empty() {
+                    true => (0..other.column_count())
+                        .filter(|c| !right_keys.contains(c))
+                        .collect(),
+                    false => columns
+                        .iter()
+                        .map(|c| {
+                            other
+                                .column_by_name(c)
+                                .ok_or(eyre!("Unknown column {c} on {name}"))
+                        })
+                        .collect::<Result<_>>()?,
+                };
+
+                let joined = join::join(
+                    &doc.matrix,
+                    &other.matrix,
+                    doc.has_header,
+                    (&left_keys, &right_keys),
+                    &columns,
+                    kind,
+                );
+                self.message = Some(Line::from(format!(
+                    "Joined {} columns, unmatched keys: {} on the table{}, \
+                     {} on {name}{}",
+                    columns.len(),
+                    joined.unmatched_left.len(),
+                    list_keys(&joined.unmatched_left),
+                    joined.unmatched_right.len(),
+                    list_keys(&joined.unmatched_right),
+                )));
+                drop(doc);
+                document.borrow_mut().replace_rows(joined.matrix, joined.rows);
+            }
+            Command::Diff { path, key } => {
+                let left = self.table().doc().matrix.clone();
+                let diff = Diff::new(
+                    self.tx.clone(),
+                    left,
+                    csv::load(&path)?,
+                    key.as_deref(),
+                )?
+                .name(Side::Left, "table")
+                .path(Side::Right, Some(path));
+                self.diff = Some(diff);
+            }
+            Command::Edit { path } => self.open(&path)?,
+            Command::NextBuffer => self.cycle_buffer(true),
+            Command::PrevBuffer => self.cycle_buffer(false),
+            Command::CloseBuffer { force } => self.close_buffer(force)?,
+            Command::Split { path, vertical } => {
+                let direction = match vertical {
+                    true => Direction::Horizontal,
+                    false => Direction::Vertical,
+                };
+                self.split(path.as_deref(), direction)?;
+            }
+            Command::Export { path, format } => {
+                let view = self.table();
+                let table = export::Table::of(&view.doc(), view.selection);
+                export::save(&path, &table, format)?;
+                self.message = Some(Line::from(format!(
+                    "Exported {} rows to {}",
+                    table.rows.len(),
+                    path.display()
+                )));
+            }
+            Command::Write { path: None } => {
+                self.table_mut().doc_mut().write()?
+            }
+            Command::Write { path: Some(path) } => {
+                self.table_mut().doc_mut().save_as(&path)?;
+                self.message =
+                    Some(Line::from(format!("Saved to {}", path.display())));
+            }
+            Command::Sheet { name: Some(name) } => self.open_sheet(&name)?,
+            Command::Sheet { name: None } => {
+                self.sheets = Some(self.sheet_picker()?);
+            }
+            Command::CloseView { force } => {
+                self.buffers[self.current].close(force)?
+            }
+            Command::OnlyView { force } => {
+                self.buffers[self.current].only(force)?
+            }
+            Command::Quit { force } => self.quit(force)?,
+        }
+        Ok(())
+    }
+
+    /// Pass the action to the query page, and close it or open its result
+    /// when it asks to.
+    fn handle_query_action(&mut self, action: Action) {
+        let Some(query) = &mut self.query else {
+            return;
+        };
+        query.handle_action(action);
+
+        if let Some(path) = query.opened.take() {
+            let result = query.csv_table.doc().matrix.clone();
+            let document =
+                Document::new(result).path(Some(path.clone())).header(true);
+            self.query = None;
+            self.push(document);
+            self.message =
+                Some(Line::from(format!("Opened {}", path.display())));
+        } else if query.closed {
+            self.query = None;
         }
     }
 }
 
+/// The first few keys, to report them on a single line.
+fn list_keys(keys: &[String]) -> String {
+    const MAX: usize = 5;
+    if keys.is_empty() {
+        return String::new();
+    }
+    let mut list =
+        keys.iter().take(MAX).cloned().collect::<Vec<_>>().join(", ");
+    if keys.len() > MAX {
+        list.push_str(", ...");
+    }
+    format!(" ({list})")
+}
+
 impl<'a> Page for Home<'a> {
     fn draw(&mut self, f: &mut Frame) {
+        if let Some(query) = &mut self.query {
+            query.draw(f);
+            return;
+        }
+        if let Some(pivot) = &mut self.pivot {
+            pivot.draw(f);
+            return;
+        }
+        if let Some(dedupe) = &mut self.dedupe {
+            dedupe.draw(f);
+            return;
+        }
+        if let Some(diff) = &mut self.diff {
+            diff.draw(f);
+            return;
+        }
+
         let areas = self.layout.split(f.size());
 
-        let text = vec![Line::from(vec![
-            "move: ".into(),
+        let titles: Vec<Line> = self
+            .buffers
+            .iter()
+            .map(|b| {
+                let doc = b.view().doc();
+                let dirty = if doc.dirty { "*" } else { "" };
+                Line::from(format!("{}{dirty}", doc.name()))
+            })
+            .collect();
+        let tabs = Tabs::new(titles)
+            .select(self.current)
+            .highlight_style(Style::new().bold().reversed());
+        f.render_widget(tabs, areas[0]);
+
+        let mut text = vec![Line::from(vec![
+            "command: ".into(),
+            ":".bold().blue(),
+            " move: ".into(),
             "hjkl".bold().blue(),
             " edit: ".into(),
             "e".bold().blue(),
             " delete: ".into(),
             "d".bold().blue(),
+            " stats: ".into(),
+            "s".bold().blue(),
+            " undo: ".into(),
+            "u".bold().blue(),
+            " yank: ".into(),
+            "y Y".bold().blue(),
+            " paste: ".into(),
+            "p".bold().blue(),
+            " buffers: ".into(),
+            "[ ]".bold().blue(),
             " quit: ".into(),
             "q".bold().blue(),
         ])];
+        if self.buffers[self.current].views.len() > 1 {
+            text[0].spans.push(" view: ".into());
+            text[0].spans.push("Tab".bold().blue());
+        }
+
+        if let Some(message) = &self.message {
+            text.push(message.clone());
+        } else if let Some(status) = self.table().status() {
+            text.push(status);
+        }
+
+        f.render_widget(Paragraph::new(text), areas[2]);
 
-        f.render_widget(Paragraph::new(text), areas[1]);
-        self.csv_table.draw(f, areas[0]);
+        if let Some(input) = &self.command_input {
+            let line = Rect { height: 1, ..areas[2] };
+            f.render_widget(Clear, line);
+            input.draw_inline(f, line, ":");
+        }
+        self.buffers[self.current].draw(f, areas[1]);
+
+        if let Some(picker) = &self.sheets {
+            self.draw_sheets(f, areas[1], picker);
+        }
     }
 
     fn get_action(&self, event: Event) -> Action {
@@ -61,28 +816,136 @@ impl<'a> Page for Home<'a> {
             Event::Tick => Action::Tick,
             Event::Render => Action::Render,
             Event::Key(key) => Action::Key(key),
+            Event::Mouse(mouse) => Action::Mouse(mouse),
+            Event::Resize(w, h) => Action::Resize(w, h),
             Event::Quit => Action::Quit,
             _ => Action::None,
         }
     }
 
     fn handle_action(&mut self, action: Action) {
+        if self.query.is_some() {
+            self.handle_query_action(action);
+            return;
+        }
+        if let Some(pivot) = &mut self.pivot {
+            pivot.handle_action(action);
+            if pivot.closed {
+                self.pivot = None;
+            }
+            return;
+        }
+        if let Some(dedupe) = &mut self.dedupe {
+            dedupe.handle_action(action);
+            if let Some(rows) = dedupe.applied.take() {
+                self.buffers[self.current].view().doc_mut().remove_rows(&rows);
+                self.message = Some(Line::from(vec![
+                    format!("Removed {} duplicate rows, undo: ", rows.len())
+                        .into(),
+                    "u".bold().blue(),
+                ]));
+                self.dedupe = None;
+            } else if dedupe.closed {
+                self.dedupe = None;
+            }
+            return;
+        }
+        let editable = self.check_editable().is_ok();
+        if let Some(diff) = &mut self.diff {
+            diff.handle_action(action);
+            if diff.closed {
+                // values copied to the table are applied as a single change,
+                // unless it is read only
+                if diff.left_changed && editable {
+                    let matrix = diff.matrix(Side::Left);
+                    self.buffers[self.current]
+                        .view()
+                        .doc_mut()
+                        .replace_matrix(matrix);
+                }
+                self.diff = None;
+            }
+            return;
+        }
+
+        if self.sheets.is_some() {
+            self.handle_sheets_action(action);
+            return;
+        }
+
+        if let Some(input) = &mut self.command_input {
+            if let Action::Key(key) = action {
+                match key.code {
+                    KeyCode::Enter => {
+                        let command = input.value.clone();
+                        self.command_input = None;
+                        if let Err(e) = self.run_command(&command) {
+                            self.message =
+                                Some(Line::from(e.to_string().red()));
+                        }
+                    }
+                    KeyCode::Esc => self.command_input = None,
+                    _ => {
+                        input.handle_action(action);
+                    }
+                }
+            }
+            return;
+        }
+
+        if let Action::Key(_) = action {
+            self.message = None;
+        }
+
+        // a click focuses the view under the mouse
+        if let Action::Mouse(m) = action {
+            if let MouseEventKind::Down(_) = m.kind {
+                let buffer = &mut self.buffers[self.current];
+                let under = buffer.views.iter().position(|v| {
+                    let a = v.area;
+                    (a.x..a.right()).contains(&m.column)
+                        && (a.y..a.bottom()).contains(&m.row)
+                });
+                if let Some(view) = under {
+                    buffer.focus(view);
+                }
+            }
+        }
+
         if let HandleActionResponse::Handle =
-            self.csv_table.handle_action(action)
+            self.table_mut().handle_action(action)
         {
-            match action {
-                Action::Key(key) => match key.code {
+            if let Action::Key(key) = action {
+                match key.code {
+                    KeyCode::Char(':') => {
+                        let input =
+                            Input::new().focused(true).mode(Mode::Insert);
+                        self.command_input = Some(input);
+                    }
+                    KeyCode::Char('y') => self.yank(false),
+                    KeyCode::Char('Y') => self.yank(true),
+                    KeyCode::Char('p') => self.paste(),
+                    KeyCode::Char(']') => self.cycle_buffer(true),
+                    KeyCode::Char('[') => self.cycle_buffer(false),
+                    KeyCode::Tab => self.focus_next(),
+                    KeyCode::BackTab => self.focus_prev(),
                     KeyCode::Char('q') | KeyCode::Esc => {
-                        self.tx.send(Action::Quit).unwrap();
+                        if let Err(e) = self.quit(false) {
+                            self.message =
+                                Some(Line::from(e.to_string().red()));
+                        }
                     }
                     _ => {}
-                },
-                _ => {}
+                }
             };
         }
     }
 
-    fn focus_next(&mut self) {}
+    fn focus_next(&mut self) {
+        self.buffers[self.current].cycle(true);
+    }
 
-    fn focus_prev(&mut self) {}
+    fn focus_prev(&mut self) {
+        self.buffers[self.current].cycle(false);
+    }
 }
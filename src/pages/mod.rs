@@ -1,5 +1,8 @@
-pub mod details;
+pub mod dedupe;
+pub mod diff;
 pub mod home;
+pub mod pivot;
+pub mod query;
 
 use ratatui::Frame;
 
@@ -0,0 +1,305 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+use crossterm::event::KeyCode;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::{
+    action::Action,
+    components::{
+        csv_table::CsvTable,
+        input::{Input, Mode},
+        Component, HandleActionResponse,
+    },
+    csv,
+    document::Document,
+    pivot::{self, Aggregate},
+    tui::Event,
+};
+
+use super::Page;
+
+/// Which side of the page gets the keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Focus {
+    Fields,
+    Grid,
+}
+
+/// Summarise a table by picking the columns to group by, on the rows and on
+/// the columns, and how to aggregate a value column.
+pub struct Pivot<'a> {
+    pub layout: Layout,
+    /// Names of the columns of the source table.
+    header: Vec<String>,
+    /// Rows of the source table, without the header.
+    body: Vec<Vec<String>>,
+    /// Row keys, in the order they were picked.
+    pub rows: Vec<usize>,
+    /// Column keys, in the order they were picked.
+    pub columns: Vec<usize>,
+    /// Column that is aggregated, rows are counted when None.
+    pub value: Option<usize>,
+    pub aggregate: Aggregate,
+    /// The field highlighted on the list.
+    field_selected: usize,
+    focus: Focus,
+    /// The pivot table, computed again when a field is picked.
+    pub csv_table: CsvTable<'a>,
+    /// Asks for the path to save the pivot table.
+    save_input: Option<Input<'a>>,
+    /// Result of the last save, shown until the next key press.
+    message: Option<Line<'static>>,
+    /// If the page should be closed, going back to the table.
+    pub closed: bool,
+}
+
+impl<'a> Pivot<'a> {
+    pub fn new(header: Vec<String>, body: Vec<Vec<String>>) -> Self {
+        let mut pivot = Self {
+            layout: Layout::new(
+                Direction::Vertical,
+                [
+                    Constraint::Min(0),    // fields and grid
+                    Constraint::Length(2), // keybindings help
+                ],
+            ),
+            header,
+            body,
+            rows: Vec::new(),
+            columns: Vec::new(),
+            value: None,
+            aggregate: Aggregate::default(),
+            field_selected: 0,
+            focus: Focus::Fields,
+            csv_table: CsvTable::from_matrix(Vec::new()),
+            save_input: None,
+            message: None,
+            closed: false,
+        };
+        pivot.update();
+        pivot
+    }
+
+    /// Compute the pivot table again.
+    fn update(&mut self) {
+        let matrix = pivot::pivot(
+            &self.header,
+            &self.body,
+            &self.rows,
+            &self.columns,
+            self.value,
+            self.aggregate,
+        );
+        let document = Document::new(matrix).header(true).shared();
+        let mut table = CsvTable::new(document).read_only(true);
+        table.focused = self.focus == Focus::Grid;
+        self.csv_table = table;
+    }
+
+    /// Add the field to the keys if it is not there, remove it otherwise.
+    fn toggle(keys: &mut Vec<usize>, field: usize) {
+        match keys.iter().position(|k| *k == field) {
+            Some(i) => {
+                keys.remove(i);
+            }
+            None => keys.push(field),
+        }
+    }
+
+    fn save(&self, path: &str) -> Result<PathBuf> {
+        let path = PathBuf::from(path.trim());
+        csv::save(&path, &self.csv_table.doc().matrix)?;
+        Ok(path)
+    }
+
+    fn handle_field_key(&mut self, code: KeyCode) {
+        let field = self.field_selected;
+        match code {
+            KeyCode::Char('j') | KeyCode::Down
+                if field + 1 < self.header.len() =>
+            {
+                self.field_selected += 1;
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.field_selected = field.saturating_sub(1);
+            }
+            KeyCode::Char('r') => {
+                Self::toggle(&mut self.rows, field);
+                self.update();
+            }
+            KeyCode::Char('c') => {
+                Self::toggle(&mut self.columns, field);
+                self.update();
+            }
+            KeyCode::Char('v') => {
+                self.value = match self.value {
+                    Some(v) if v == field => None,
+                    _ => Some(field),
+                };
+                self.update();
+            }
+            KeyCode::Char('a') => {
+                self.aggregate = self.aggregate.next();
+                self.update();
+            }
+            _ => {}
+        }
+    }
+
+    fn draw_fields(&self, f: &mut Frame, rect: Rect) {
+        let items: Vec<ListItem> = self
+            .header
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let mut line = vec![Span::from(name.clone())];
+                if let Some(n) = self.rows.iter().position(|r| *r == i) {
+                    line.push(format!(" row {}", n + 1).green());
+                }
+                if let Some(n) = self.columns.iter().position(|c| *c == i) {
+                    line.push(format!(" col {}", n + 1).blue());
+                }
+                if self.value == Some(i) {
+                    line.push(format!(" {}", self.aggregate.name()).yellow());
+                }
+                ListItem::new(Line::from(line))
+            })
+            .collect();
+
+        let mut state = ListState::default().with_selected(
+            Some(self.field_selected).filter(|_| self.focus == Focus::Fields),
+        );
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(format!("Fields, {}", self.aggregate.name()))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded),
+            )
+            .highlight_style(Style::default().reversed());
+        f.render_stateful_widget(list, rect, &mut state);
+    }
+}
+
+impl<'a> Page for Pivot<'a> {
+    fn draw(&mut self, f: &mut Frame) {
+        let areas = self.layout.split(f.size());
+        let [fields, grid] = *Layout::new(
+            Direction::Horizontal,
+            [Constraint::Length(30), Constraint::Min(0)],
+        )
+        .split(areas[0]) else {
+            return;
+        };
+
+        self.draw_fields(f, fields);
+        let grid =
+            Rect { x: grid.x + 1, width: grid.width.saturating_sub(1), ..grid };
+        self.csv_table.draw(f, grid);
+
+        let mut text = vec![Line::from(vec![
+            "row key: ".into(),
+            "r".bold().blue(),
+            " column key: ".into(),
+            "c".bold().blue(),
+            " value: ".into(),
+            "v".bold().blue(),
+            " aggregate: ".into(),
+            "a".bold().blue(),
+            " switch: ".into(),
+            "Tab".bold().blue(),
+            " save: ".into(),
+            "w".bold().blue(),
+            " back: ".into(),
+            "q".bold().blue(),
+        ])];
+        if let Some(message) = &self.message {
+            text.push(message.clone());
+        }
+        f.render_widget(Paragraph::new(text), areas[1]);
+
+        if let Some(input) = &self.save_input {
+            let line = Rect { height: 1, ..areas[1] };
+            f.render_widget(Clear, line);
+            input.draw_inline(f, line, "save to: ");
+        }
+    }
+
+    fn get_action(&self, event: Event) -> Action {
+        match event {
+            Event::Error => Action::None,
+            Event::Tick => Action::Tick,
+            Event::Render => Action::Render,
+            Event::Key(key) => Action::Key(key),
+            Event::Resize(w, h) => Action::Resize(w, h),
+            Event::Quit => Action::Quit,
+            _ => Action::None,
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        if let Some(input) = &mut self.save_input {
+            if let Action::Key(key) = action {
+                match key.code {
+                    KeyCode::Enter => {
+                        let path = input.value.clone();
+                        self.save_input = None;
+                        self.message = Some(match self.save(&path) {
+                            Ok(path) => {
+                                Line::from(format!("Saved {}", path.display()))
+                            }
+                            Err(e) => Line::from(e.to_string().red()),
+                        });
+                    }
+                    KeyCode::Esc => self.save_input = None,
+                    _ => {
+                        input.handle_action(action);
+                    }
+                }
+            }
+            return;
+        }
+
+        let Action::Key(key) = action else {
+            return;
+        };
+        self.message = None;
+
+        if self.focus == Focus::Grid {
+            if let HandleActionResponse::Ignore =
+                self.csv_table.handle_action(action)
+            {
+                return;
+            }
+            // hjkl move on the grid
+            if let KeyCode::Char('h' | 'j' | 'k' | 'l') = key.code {
+                return;
+            }
+        }
+
+        match key.code {
+            KeyCode::Tab => self.focus_next(),
+            KeyCode::BackTab => self.focus_prev(),
+            KeyCode::Char('w') => {
+                let input = Input::new().focused(true).mode(Mode::Insert);
+                self.save_input = Some(input);
+            }
+            KeyCode::Char('q') | KeyCode::Esc => self.closed = true,
+            code if self.focus == Focus::Fields => self.handle_field_key(code),
+            _ => {}
+        }
+    }
+
+    fn focus_next(&mut self) {
+        self.focus = match self.focus {
+            Focus::Fields => Focus::Grid,
+            Focus::Grid => Focus::Fields,
+        };
+        self.csv_table.focused = self.focus == Focus::Grid;
+    }
+
+    fn focus_prev(&mut self) {
+        self.focus_next();
+    }
+}
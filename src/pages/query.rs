@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+use crossterm::event::KeyCode;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::{
+    action::Action,
+    components::{
+        csv_table::CsvTable,
+        input::{Input, Mode},
+        Component, HandleActionResponse,
+    },
+    csv,
+    document::Document,
+    tui::Event,
+};
+
+use super::Page;
+
+/// What is done with the path typed on the prompt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Prompt {
+    Export,
+    Open,
+}
+
+/// The result of an SQL query, shown on a read only table.
+pub struct Query<'a> {
+    pub layout: Layout,
+    pub sql: String,
+    pub csv_table: CsvTable<'a>,
+    /// Asks for a path to export or open the result.
+    prompt: Option<(Prompt, Input<'a>)>,
+    /// Result of the last export, shown until the next key press.
+    pub message: Option<Line<'static>>,
+    /// If the page should be closed, going back to the table.
+    pub closed: bool,
+    /// The result was saved to this path and should be opened on a new
+    /// buffer.
+    pub opened: Option<PathBuf>,
+}
+
+impl<'a> Query<'a> {
+    /// `result` holds the names of the columns on the first row.
+    pub fn new(sql: String, result: Vec<Vec<String>>) -> Self {
+        Self {
+            layout: Layout::new(
+                Direction::Vertical,
+                [
+                    Constraint::Length(2), // the query
+                    Constraint::Min(0),    // cells
+                    Constraint::Length(2), // keybindings help
+                ],
+            ),
+            sql,
+            csv_table: CsvTable::new(
+                Document::new(result).header(true).shared(),
+            )
+            .read_only(true),
+            prompt: None,
+            message: None,
+            closed: false,
+            opened: None,
+        }
+    }
+
+    /// Write the result as CSV.
+    fn export(&self, path: &str) -> Result<PathBuf> {
+        let path = PathBuf::from(path.trim());
+        csv::save(&path, &self.csv_table.doc().matrix)?;
+        Ok(path)
+    }
+
+    fn run_prompt(&mut self, prompt: Prompt, path: &str) {
+        match (prompt, self.export(path)) {
+            (_, Err(e)) => self.message = Some(Line::from(e.to_string().red())),
+            (Prompt::Export, Ok(path)) => {
+                self.message = Some(Line::from(format!(
+                    "Exported {} rows to {}",
+                    self.csv_table.doc().body().len(),
+                    path.display()
+                )));
+            }
+            (Prompt::Open, Ok(path)) => self.opened = Some(path),
+        }
+    }
+}
+
+impl<'a> Page for Query<'a> {
+    fn draw(&mut self, f: &mut Frame) {
+        let areas = self.layout.split(f.size());
+
+        f.render_widget(
+            Paragraph::new(self.sql.clone().italic()).wrap(Wrap { trim: true }),
+            areas[0],
+        );
+
+        let mut text = vec![Line::from(vec![
+            "move: ".into(),
+            "hjkl".bold().blue(),
+            " stats: ".into(),
+            "s".bold().blue(),
+            " export: ".into(),
+            "w".bold().blue(),
+            " open: ".into(),
+            "o".bold().blue(),
+            " back: ".into(),
+            "q".bold().blue(),
+        ])];
+        if let Some(message) = &self.message {
+            text.push(message.clone());
+        }
+        f.render_widget(Paragraph::new(text), areas[2]);
+
+        if let Some((prompt, input)) = &self.prompt {
+            let line = Rect { height: 1, ..areas[2] };
+            f.render_widget(Clear, line);
+            let prompt = match prompt {
+                Prompt::Export => "export to: ",
+                Prompt::Open => "save and open: ",
+            };
+            input.draw_inline(f, line, prompt);
+        }
+        self.csv_table.draw(f, areas[1]);
+    }
+
+    fn get_action(&self, event: Event) -> Action {
+        match event {
+            Event::Error => Action::None,
+            Event::Tick => Action::Tick,
+            Event::Render => Action::Render,
+            Event::Key(key) => Action::Key(key),
+            Event::Resize(w, h) => Action::Resize(w, h),
+            Event::Quit => Action::Quit,
+            _ => Action::None,
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        if let Some((prompt, input)) = &mut self.prompt {
+            if let Action::Key(key) = action {
+                match key.code {
+                    KeyCode::Enter => {
+                        let (prompt, path) = (*prompt, input.value.clone());
+                        self.prompt = None;
+                        self.run_prompt(prompt, &path);
+                    }
+                    KeyCode::Esc => self.prompt = None,
+                    _ => {
+                        input.handle_action(action);
+                    }
+                }
+            }
+            return;
+        }
+
+        if let Action::Key(_) = action {
+            self.message = None;
+        }
+
+        if let HandleActionResponse::Handle =
+            self.csv_table.handle_action(action)
+        {
+            if let Action::Key(key) = action {
+                let prompt = match key.code {
+                    KeyCode::Char('w') => Some(Prompt::Export),
+                    KeyCode::Char('o') => Some(Prompt::Open),
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        self.closed = true;
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(prompt) = prompt {
+                    let input = Input::new().focused(true).mode(Mode::Insert);
+                    self.prompt = Some((prompt, input));
+                }
+            }
+        }
+    }
+
+    fn focus_next(&mut self) {}
+
+    fn focus_prev(&mut self) {}
+}
@@ -0,0 +1,50 @@
+use std::{
+    io::{IsTerminal, Read},
+    path::Path,
+};
+
+use color_eyre::eyre::{Result, WrapErr};
+
+use crate::csv;
+
+/// A file named `-` is read from stdin, and written to stdout.
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Read a table from stdin until it is closed.
+pub fn read_stdin() -> Result<Vec<Vec<String>>> {
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .wrap_err("Could not read stdin")?;
+    Ok(csv::parse(&text))
+}
+
+/// Point stdin to the terminal after the table was read from a pipe, so the
+/// keys are read from the keyboard.
+#[cfg(unix)]
+pub fn reopen_tty() -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    if std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let tty = std::fs::File::options()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .wrap_err("No terminal to read the keys from")?;
+    // SAFETY: both are open file descriptors, stdin is replaced by a copy of
+    // the terminal that stays open after `tty` is dropped
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } == -1 {
+        return Err(std::io::Error::last_os_error())
+            .wrap_err("Could not read the keys from the terminal");
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn reopen_tty() -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use itertools::Itertools;
+
+/// How the values falling on a cell of a pivot table are summarised.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Aggregate {
+    #[default]
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub const ALL: [Aggregate; 5] = [
+        Aggregate::Count,
+        Aggregate::Sum,
+        Aggregate::Avg,
+        Aggregate::Min,
+        Aggregate::Max,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "count",
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+
+    /// The aggregate after this one, wrapping around.
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|a| a == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// Summarise the values, empty ones are left out. Sum and avg only use
+    /// the values that are numbers, min and max compare numbers as numbers.
+    pub fn apply(&self, values: &[&str]) -> String {
+        let present = values.iter().copied().filter(|v| !v.trim().is_empty());
+
+        match self {
+            Aggregate::Count => present.count().to_string(),
+            Aggregate::Sum | Aggregate::Avg => {
+                let numbers: Vec<f64> =
+                    present.filter_map(|v| v.trim().parse().ok()).collect();
+                if numbers.is_empty() {
+                    return String::new();
+                }
+                let sum: f64 = numbers.iter().sum();
+                match self {
+                    Aggregate::Avg => (sum / numbers.len() as f64).to_string(),
+                    _ => sum.to_string(),
+                }
+            }
+            Aggregate::Min => {
+                present.min_by(|a, b| compare(a, b)).unwrap_or("").to_string()
+            }
+            Aggregate::Max => {
+                present.max_by(|a, b| compare(a, b)).unwrap_or("").to_string()
+            }
+        }
+    }
+}
+
+/// Compare two values as numbers when both are, otherwise as text.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+fn compare_keys(a: &[String], b: &[String]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| compare(a, b))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Group the rows of `body` by the values of the `rows` columns, spread them
+/// over the values of the `columns` columns, and summarise the `value`
+/// column on each cell. Without a value column every row counts as 1.
+///
+/// The first row of the result is the header, the names of the row keys
+/// followed by the values of the column keys, joined with ` / `.
+pub fn pivot(
+    header: &[String],
+    body: &[Vec<String>],
+    rows: &[usize],
+    columns: &[usize],
+    value: Option<usize>,
+    aggregate: Aggregate,
+) -> Vec<Vec<String>> {
+    let key = |row: &[String], cols: &[usize]| -> Vec<String> {
+        cols.iter().map(|c| row.get(*c).cloned().unwrap_or_default()).collect()
+    };
+
+    let mut cells: HashMap<(Vec<String>, Vec<String>), Vec<&str>> =
+        HashMap::new();
+    for row in body {
+        let value = match value {
+            Some(col) => row.get(col).map_or("", |v| v.as_str()),
+            None => "1",
+        };
+        cells
+            .entry((key(row, rows), key(row, columns)))
+            .or_default()
+            .push(value);
+    }
+
+    let row_keys: Vec<&Vec<String>> = cells
+        .keys()
+        .map(|(r, _)| r)
+        .unique()
+        .sorted_by(|a, b| compare_keys(a, b))
+        .collect();
+    let column_keys: Vec<&Vec<String>> = cells
+        .keys()
+        .map(|(_, c)| c)
+        .unique()
+        .sorted_by(|a, b| compare_keys(a, b))
+        .collect();
+
+    let name = |col: usize| header.get(col).cloned().unwrap_or_default();
+    let mut result: Vec<Vec<String>> = vec![rows
+        .iter()
+        .map(|col| name(*col))
+        .chain(column_keys.iter().map(|key| match columns.is_empty() {
+            true => match value {
+                Some(col) => format!("{}({})", aggregate.name(), name(col)),
+                None => aggregate.name().to_string(),
+            },
+            false => key.join(" / "),
+        }))
+        .collect()];
+
+    for row_key in row_keys {
+        let mut row = row_key.clone();
+        for column_key in &column_keys {
+            let values = cells.get(&(row_key.clone(), (*column_key).clone()));
+            row.push(values.map(|v| aggregate.apply(v)).unwrap_or_default());
+        }
+        result.push(row);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|r| r.iter().map(|v| v.to_string()).collect()).collect()
+    }
+
+    fn sales() -> (Vec<String>, Vec<Vec<String>>) {
+        let header = matrix(&[&["region", "year", "amount"]]).remove(0);
+        let body = matrix(&[
+            &["north", "2024", "10"],
+            &["south", "2023", "5"],
+            &["north", "2023", "2"],
+            &["north", "2024", "x"],
+            &["south", "2024", ""],
+        ]);
+        (header, body)
+    }
+
+    #[test]
+    fn aggregates() {
+        let values = ["10", "9", "", "x", "2.5"];
+        assert_eq!(Aggregate::Count.apply(&values), "4");
+        assert_eq!(Aggregate::Sum.apply(&values), "21.5");
+        assert_eq!(Aggregate::Avg.apply(&["1", "2"]), "1.5");
+        assert_eq!(Aggregate::Min.apply(&["10", "9", "2.5"]), "2.5");
+        assert_eq!(Aggregate::Max.apply(&["10", "9", "2.5"]), "10");
+        assert_eq!(Aggregate::Max.apply(&["b", "a"]), "b");
+        assert_eq!(Aggregate::Sum.apply(&["x", ""]), "");
+        assert_eq!(Aggregate::Min.apply(&[]), "");
+    }
+
+    #[test]
+    fn next_wraps_around() {
+        assert_eq!(Aggregate::Count.next(), Aggregate::Sum);
+        assert_eq!(Aggregate::Max.next(), Aggregate::Count);
+    }
+
+    #[test]
+    fn rows_by_columns() {
+        let (header, body) = sales();
+        let result = pivot(&header, &body, &[0], &[1], Some(2), Aggregate::Sum);
+
+        assert_eq!(
+            result,
+            matrix(&[
+                &["region", "2023", "2024"],
+                &["north", "2", "10"],
+                &["south", "5", ""],
+            ])
+        );
+    }
+
+    #[test]
+    fn count_without_column_keys() {
+        let (header, body) = sales();
+
+        let result = pivot(&header, &body, &[1], &[], None, Aggregate::Count);
+        assert_eq!(
+            result,
+            matrix(&[&["year", "count"], &["2023", "2"], &["2024", "3"]])
+        );
+
+        let result =
+            pivot(&header, &body, &[0], &[], Some(2), Aggregate::Count);
+        assert_eq!(
+            result,
+            matrix(&[
+                &["region", "count(amount)"],
+                &["north", "3"],
+                &["south", "1"],
+            ])
+        );
+    }
+
+    #[test]
+    fn keys_sort_as_numbers() {
+        let header = matrix(&[&["n"]]).remove(0);
+        let body = matrix(&[&["10"], &["9"], &["10"]]);
+        let result = pivot(&header, &body, &[0], &[], None, Aggregate::Count);
+
+        assert_eq!(
+            result,
+            matrix(&[&["n", "count"], &["9", "1"], &["10", "2"]])
+        );
+    }
+}
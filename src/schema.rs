@@ -0,0 +1,307 @@
+use std::{collections::HashMap, path::Path};
+
+use color_eyre::eyre::{Result, WrapErr};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::{types::ColumnType, utils::column_name};
+
+/// The contract a table must follow, loaded from a JSON5 file like:
+///
+/// ```json5
+/// {
+///   header: true,
+///   columns: [
+///     { name: "id", type: "integer", nullable: false, unique: true },
+///     { name: "status", enum: ["open", "closed"] },
+///     { name: "qty", type: "integer", min: 0, max: 1000 },
+///     { name: "email", type: "email", regex: "@example\\.com$" },
+///   ],
+/// }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    /// If the first row is a header. When it is, columns are matched by
+    /// name, otherwise by their position on the schema.
+    #[serde(default)]
+    pub header: Option<bool>,
+    pub columns: Vec<ColumnSchema>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub column_type: Option<ColumnType>,
+    /// The column must be present on the table.
+    #[serde(default = "default_true")]
+    pub required: bool,
+    /// The column can have empty values.
+    #[serde(default = "default_true")]
+    pub nullable: bool,
+    /// The only values allowed.
+    #[serde(default, rename = "enum")]
+    pub values: Option<Vec<String>>,
+    /// A pattern that every non empty value must match.
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub regex: Option<Regex>,
+    /// Smallest number allowed, inclusive.
+    #[serde(default)]
+    pub min: Option<f64>,
+    /// Biggest number allowed, inclusive.
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// No two rows can have the same non empty value.
+    #[serde(default)]
+    pub unique: bool,
+    /// How many characters the column has on a fixed width file.
+    #[serde(default)]
+    pub width: Option<usize>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(pattern) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    Regex::new(&pattern).map(Some).map_err(serde::de::Error::custom)
+}
+
+/// A rule of the schema that the table breaks.
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// The offending cell (row, col), `None` when the whole column is
+    /// missing.
+    pub cell: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Schema {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Could not read {}", path.display()))?;
+        json5::from_str(&text)
+            .wrap_err_with(|| format!("Invalid schema {}", path.display()))
+    }
+
+    /// The widths of the columns of a fixed width file, when every column
+    /// has one.
+    pub fn widths(&self) -> Option<Vec<usize>> {
+        let widths: Option<Vec<usize>> =
+            self.columns.iter().map(|c| c.width).collect();
+        widths.filter(|w| !w.is_empty())
+    }
+
+    /// Validate the whole matrix against the schema. `has_header` is used
+    /// when the schema does not say if there is a header.
+    pub fn validate(
+        &self,
+        matrix: &[Vec<String>],
+        has_header: bool,
+    ) -> Vec<Violation> {
+        let has_header = self.header.unwrap_or(has_header);
+        let header = if has_header { matrix.first() } else { None };
+        let start = if has_header { 1 } else { 0 };
+
+        let mut violations = Vec::new();
+
+        for (i, column) in self.columns.iter().enumerate() {
+            let col = match header {
+                Some(header) => header.iter().position(|n| *n == column.name),
+                None => Some(i),
+            };
+            let col = col.filter(|col| {
+                matrix.iter().skip(start).any(|row| row.len() > *col)
+            });
+
+            let Some(col) = col else {
+                if column.required {
+                    violations.push(Violation {
+                        cell: None,
+                        message: format!("Missing column {}", column.name),
+                    });
+                }
+                continue;
+            };
+
+            let mut seen: HashMap<&str, usize> = HashMap::new();
+
+            for (row, values) in matrix.iter().enumerate().skip(start) {
+                let value = values.get(col).map_or("", |v| v.as_str());
+
+                let mut violate = |message: String| {
+                    violations.push(Violation {
+                        cell: Some((row, col)),
+                        message: format!(
+                            "{}{}: {message}",
+                            column_name(col),
+                            row + 1
+                        ),
+                    })
+                };
+
+                if value.trim().is_empty() {
+                    if !column.nullable {
+                        violate(format!("{} can not be empty", column.name));
+                    }
+                    continue;
+                }
+
+                for message in column.check(value) {
+                    violate(message);
+                }
+
+                if column.unique {
+                    if let Some(first) = seen.get(value) {
+                        violate(format!(
+                            "{value:?} is repeated, first seen on row {}",
+                            first + 1
+                        ));
+                    } else {
+                        seen.insert(value, row);
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+impl ColumnSchema {
+    /// Check a non empty value against the rules of the column that only
+    /// depend on the value itself.
+    fn check(&self, value: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if let Some(t) = self.column_type {
+            if !t.accepts(value) {
+                errors.push(format!("{value:?} is not a valid {}", t.name()));
+            }
+        }
+
+        if let Some(values) = &self.values {
+            if !values.iter().any(|v| v == value) {
+                errors.push(format!(
+                    "{value:?} is not one of {}",
+                    values.join(", ")
+                ));
+            }
+        }
+
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(value) {
+                errors.push(format!("{value:?} does not match {regex}"));
+            }
+        }
+
+        if self.min.is_some() || self.max.is_some() {
+            let Ok(n) = value.trim().parse::<f64>() else {
+                errors.push(format!("{value:?} is not a number"));
+                return errors;
+            };
+            if let Some(min) = self.min.filter(|min| n < *min) {
+                errors.push(format!("{value} is less than {min}"));
+            }
+            if let Some(max) = self.max.filter(|max| n > *max) {
+                errors.push(format!("{value} is more than {max}"));
+            }
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(schema: &str, rows: &[&[&str]]) -> Vec<String> {
+        let schema: Schema = json5::from_str(schema).unwrap();
+        let matrix: Vec<Vec<String>> = rows
+            .iter()
+            .map(|r| r.iter().map(|v| v.to_string()).collect())
+            .collect();
+        schema.validate(&matrix, true).into_iter().map(|v| v.message).collect()
+    }
+
+    #[test]
+    fn valid_table() {
+        let schema = r#"{ columns: [
+            { name: "id", type: "integer", nullable: false, unique: true },
+            { name: "qty", min: 0, max: 10 },
+        ] }"#;
+        let rows: &[&[&str]] = &[&["id", "qty"], &["1", "0"], &["2", "10"]];
+        assert!(messages(schema, rows).is_empty());
+    }
+
+    #[test]
+    fn every_rule() {
+        let schema = r#"{ columns: [
+            { name: "id", type: "integer", nullable: false, unique: true },
+            { name: "status", enum: ["open", "closed"] },
+            { name: "qty", min: 0, max: 10 },
+            { name: "email", regex: "@example\\.com$" },
+            { name: "missing" },
+            { name: "optional", required: false },
+        ] }"#;
+        let rows: &[&[&str]] = &[
+            &["id", "status", "qty", "email"],
+            &["1", "open", "5", "a@example.com"],
+            &["1", "done", "-1", "b@other.com"],
+            &["x", "", "many", ""],
+            &["", "closed", "11", ""],
+        ];
+        assert_eq!(
+            messages(schema, rows),
+            [
+                "A3: \"1\" is repeated, first seen on row 2",
+                "A4: \"x\" is not a valid integer",
+                "A5: id can not be empty",
+                "B3: \"done\" is not one of open, closed",
+                "C3: -1 is less than 0",
+                "C4: \"many\" is not a number",
+                "C5: 11 is more than 10",
+                "D3: \"b@other.com\" does not match @example\\.com$",
+                "Missing column missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_by_position_without_header() {
+        let schema = r#"{ header: false, columns: [
+            { name: "n", type: "integer" }, { name: "b", type: "boolean" },
+        ] }"#;
+        let rows: &[&[&str]] = &[&["1", "yes"], &["a", "no"]];
+        assert_eq!(
+            messages(schema, rows),
+            ["A2: \"a\" is not a valid integer"]
+        );
+    }
+
+    #[test]
+    fn widths_need_every_column() {
+        let schema: Schema = json5::from_str(
+            r#"{ columns: [{ name: "a", width: 3 }, { name: "b", width: 5 }] }"#,
+        )
+        .unwrap();
+        assert_eq!(schema.widths(), Some(vec![3, 5]));
+        let schema: Schema = json5::from_str(
+            r#"{ columns: [{ name: "a", width: 3 }, { name: "b" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(schema.widths(), None);
+        assert!(
+            json5::from_str::<Schema>(r#"{ columns: [], typo: 1 }"#).is_err()
+        );
+    }
+}
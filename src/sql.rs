@@ -0,0 +1,168 @@
+use color_eyre::eyre::{eyre, Result};
+use rusqlite::{limits::Limit, types::ValueRef, Connection, ToSql};
+
+use crate::types::ColumnType;
+
+/// Name of the table the query runs against.
+pub const TABLE: &str = "this";
+
+/// The SQLite type used to store a column of a given type.
+pub fn sql_type(t: ColumnType) -> &'static str {
+    match t {
+        ColumnType::Integer => "INTEGER",
+        ColumnType::Float => "REAL",
+        _ => "TEXT",
+    }
+}
+
+/// Quote an identifier, so column names can have spaces and keywords.
+pub fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Make the column names valid and unique, empty names become the
+/// spreadsheet style name of the column.
+pub fn column_names(names: &[String]) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        let name = match name.trim() {
+            "" => crate::utils::column_name(i),
+            name => name.to_string(),
+        };
+        let mut candidate = name.clone();
+        let mut n = 2;
+        while unique.iter().any(|u| u.eq_ignore_ascii_case(&candidate)) {
+            candidate = format!("{name}_{n}");
+            n += 1;
+        }
+        unique.push(candidate);
+    }
+    unique
+}
+
+/// The statement that creates `table`, with a column of the SQLite type of
+/// each column type.
+pub fn create_statement(
+    table: &str,
+    columns: &[String],
+    types: &[ColumnType],
+) -> String {
+    let definition = columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let t = types.get(i).copied().unwrap_or_default();
+            format!("{} {}", quote(name), sql_type(t))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("CREATE TABLE {} ({definition})", quote(table))
+}
+
+/// Create `table` with the given columns and insert the rows. Empty cells
+/// are stored as NULL, and numbers as numbers when the column is numeric.
+pub fn create_table(
+    conn: &Connection,
+    table: &str,
+    columns: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+) -> Result<()> {
+    conn.execute(&create_statement(table, columns, types), [])?;
+
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} VALUES ({placeholders})",
+            quote(table)
+        ))?;
+        for row in rows {
+            let values: Vec<Box<dyn ToSql>> = (0..columns.len())
+                .map(|i| {
+                    let value = row.get(i).map_or("", |v| v.as_str());
+                    let t = types.get(i).copied().unwrap_or_default();
+                    to_sql(value, t)
+                })
+                .collect();
+            insert.execute(rusqlite::params_from_iter(values))?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn to_sql(value: &str, t: ColumnType) -> Box<dyn ToSql> {
+    if value.is_empty() {
+        return Box::new(None::<String>);
+    }
+    match t {
+        ColumnType::Integer => match value.trim().parse::<i64>() {
+            Ok(n) => Box::new(n),
+            Err(_) => Box::new(value.to_string()),
+        },
+        ColumnType::Float => match value.trim().parse::<f64>() {
+            Ok(n) => Box::new(n),
+            Err(_) => Box::new(value.to_string()),
+        },
+        _ => Box::new(value.to_string()),
+    }
+}
+
+/// Text of a value read from SQLite, NULL is an empty cell.
+pub fn to_cell(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(n) => n.to_string(),
+        ValueRef::Text(s) => String::from_utf8_lossy(s).into_owned(),
+        ValueRef::Blob(b) => b.iter().map(|b| format!("{b:02x}")).collect(),
+    }
+}
+
+/// Run a query and return its result, the first row holds the names of the
+/// columns. Statements that would change the database, or attach or write
+/// another one, are refused.
+pub fn select(conn: &Connection, sql: &str) -> Result<Vec<Vec<String>>> {
+    conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0);
+    let mut stmt = conn.prepare(sql)?;
+    if !stmt.readonly() {
+        return Err(eyre!("Only queries that read the table can be run"));
+    }
+    let names: Vec<String> =
+        stmt.column_names().into_iter().map(|n| n.to_string()).collect();
+    let count = names.len();
+
+    let mut matrix = vec![names];
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let mut cells = Vec::with_capacity(count);
+        for i in 0..count {
+            cells.push(to_cell(row.get_ref(i)?));
+        }
+        matrix.push(cells);
+    }
+    Ok(matrix)
+}
+
+/// Run a query over a table, loaded on an in memory database as `this`.
+/// Columns are named by `header`, and typed by `types`.
+pub fn query(
+    header: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+    sql: &str,
+) -> Result<Vec<Vec<String>>> {
+    select(&open_table(header, types, rows)?, sql)
+}
+
+/// An in memory database with the table loaded as `this`.
+pub fn open_table(
+    header: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+) -> Result<Connection> {
+    let conn = Connection::open_in_memory()?;
+    create_table(&conn, TABLE, &column_names(header), types, rows)?;
+    Ok(conn)
+}
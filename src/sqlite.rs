@@ -0,0 +1,409 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use rusqlite::{
+    params_from_iter,
+    types::{Value, ValueRef},
+    Connection, OpenFlags,
+};
+
+use crate::{
+    sql::{self, quote},
+    types::ColumnType,
+};
+
+/// The table of a SQLite database a document was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub table: String,
+    /// The names of every table of the database.
+    pub tables: Vec<String>,
+    /// The columns of the table on the database, in the order of the values
+    /// of `rows`.
+    pub columns: Vec<String>,
+    /// The rows below the header as they are on the database, with their
+    /// `rowid` and `None` for NULL, by the id of their row on the document,
+    /// to write back only the cells that changed. `None` until the table is
+    /// written, it is created then, replacing a table of the same name.
+    pub rows: Option<HashMap<usize, Row>>,
+    /// The name the `rowid` of the table is read by, one of its names that
+    /// is not a column. `None` for a table WITHOUT ROWID, or with columns by
+    /// every one of its names, its rows can not be told apart to write them
+    /// back.
+    pub rowid: Option<String>,
+}
+
+/// A row as it is on the database, its `rowid` and its values.
+pub type Row = (i64, Vec<Option<String>>);
+
+impl Source {
+    /// A table that is not on the database yet.
+    pub fn new(table: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            tables: vec![table.into()],
+            columns: Vec::new(),
+            rows: None,
+            rowid: Some("rowid".into()),
+        }
+    }
+}
+
+/// Read a table of a database, or its first table. The first row holds the
+/// names of the columns, and NULL is an empty cell.
+pub fn load(
+    path: &Path,
+    table: Option<&str>,
+) -> Result<(Vec<Vec<String>>, Source)> {
+    let conn = open(path)?;
+    let tables = tables(&conn)?;
+    let table = match table {
+        Some(name) if tables.iter().any(|t| t == name) => name.to_string(),
+        Some(name) => {
+            return Err(eyre!("No table {name} on {}", path.display()))
+        }
+        None => tables
+            .first()
+            .cloned()
+            .ok_or(eyre!("{} has no tables", path.display()))?,
+    };
+
+    let read = || -> Result<_> {
+        // a table without a rowid is read with 0 for it, and is read only
+        let rowid = rowid(&conn, &table)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, * FROM {}",
+            rowid.as_deref().unwrap_or("0"),
+            quote(&table)
+        ))?;
+        let columns: Vec<String> = stmt
+            .column_names()
+            .into_iter()
+            .skip(1)
+            .map(|n| n.to_string())
+            .collect();
+        let count = columns.len();
+
+        // the rowid and NULLs are kept on the source, out of the table, by
+        // the index of the row on the matrix, which is its id on the document
+        let mut matrix = vec![columns.clone()];
+        let mut rows = HashMap::new();
+        let mut results = stmt.query([])?;
+        while let Some(row) = results.next()? {
+            let values = (1..=count)
+                .map(|i| match row.get_ref(i)? {
+                    ValueRef::Null => Ok(None),
+                    value => Ok(Some(sql::to_cell(value))),
+                })
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            matrix.push(
+                values.iter().map(|v| v.clone().unwrap_or_default()).collect(),
+            );
+            rows.insert(matrix.len() - 1, (row.get(0)?, values));
+        }
+        Ok((
+            matrix,
+            Source {
+                table: table.clone(),
+                tables,
+                columns,
+                rows: Some(rows),
+                rowid,
+            },
+        ))
+    };
+    read().wrap_err_with(|| format!("Could not read {table}"))
+}
+
+/// Write the rows back to the table, as the statements that turn the rows
+/// of `source` into them, in a single transaction. Rows are told by their
+/// `ids` on the document, a row that did not change is left as it is, even
+/// when it moved, so sorting writes nothing. Only the cells that changed are
+/// updated, new rows are inserted and the rows that are gone deleted.
+/// Columns that are not on the table are added to it, and a column with
+/// another name than it had, in the same place, is renamed. Refused for a
+/// table that has no rowid.
+pub fn write(
+    path: &Path,
+    source: &mut Source,
+    columns: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+    ids: &[usize],
+) -> Result<()> {
+    let mut conn = Connection::open(path)
+        .wrap_err_with(|| format!("Could not open {}", path.display()))?;
+    let tx = conn.transaction()?;
+    let table = quote(&source.table);
+    let columns = sql::column_names(columns);
+
+    let old = match &source.rows {
+        Some(_) if source.rowid.is_none() => {
+            return Err(eyre!(
+                "Table {} has no rowid to tell its rows apart, they can not \
+                 be written back",
+                source.table
+            ))
+        }
+        Some(rows) => rows.clone(),
+        None => {
+            tx.execute(&format!("DROP TABLE IF EXISTS {table}"), [])?;
+            tx.execute(
+                &sql::create_statement(&source.table, &columns, types),
+                [],
+            )?;
+            HashMap::new()
+        }
+    };
+
+    // a column named anew where the old name is gone from the header, and
+    // the new one is not on the table, was renamed and keeps its values
+    let has = |names: &[String], name: &str| {
+        names.iter().any(|n| n.eq_ignore_ascii_case(name))
+    };
+    let mut read = source.columns.clone();
+    for (name, old) in columns.iter().zip(&mut read) {
+        if !old.eq_ignore_ascii_case(name)
+            && !has(&columns, old)
+            && !has(&source.columns, name)
+        {
+            tx.execute(
+                &format!(
+                    "ALTER TABLE {table} RENAME COLUMN {} TO {}",
+                    quote(old),
+                    quote(name)
+                ),
+                [],
+            )?;
+            *old = name.clone();
+        }
+    }
+
+    let existing = table_columns(&tx, &source.table)?;
+    for (i, name) in columns.iter().enumerate() {
+        if !existing.iter().any(|(e, _)| e.eq_ignore_ascii_case(name)) {
+            let t = sql::sql_type(types.get(i).copied().unwrap_or_default());
+            tx.execute(
+                &format!("ALTER TABLE {table} ADD COLUMN {} {t}", quote(name)),
+                [],
+            )?;
+        }
+    }
+    // the name to tell rows by, a column added may be named like the one
+    // the rowid was read by
+    let by = rowid(&tx, &source.table)?.ok_or(eyre!(
+        "Table {} has columns by every name of its rowid",
+        source.table
+    ))?;
+
+    // empty cells are NULL, but on NOT NULL columns, and SQLite stores the
+    // rest by the column type
+    let position = |names: &[String], name: &str| {
+        names.iter().position(|n| n.eq_ignore_ascii_case(name))
+    };
+    let not_null: Vec<bool> = columns
+        .iter()
+        .map(|name| {
+            existing
+                .iter()
+                .find(|(e, _)| e.eq_ignore_ascii_case(name))
+                .is_some_and(|(_, not_null)| *not_null)
+        })
+        .collect();
+    let value = |c: usize, r: &[String]| -> Option<String> {
+        let v = r.get(c).cloned().unwrap_or_default();
+        Some(v).filter(|v| !v.is_empty() || not_null[c])
+    };
+    let bind = |v: &Option<String>| match v {
+        Some(v) => Value::Text(v.clone()),
+        None => Value::Null,
+    };
+
+    let names: Vec<String> = columns.iter().map(|c| quote(c)).collect();
+    let mut insert = tx.prepare(&format!(
+        "INSERT INTO {table} ({}) VALUES ({})",
+        names.join(", "),
+        vec!["?"; columns.len()].join(", ")
+    ))?;
+    let mut delete =
+        tx.prepare(&format!("DELETE FROM {table} WHERE {by} = ?"))?;
+
+    // the rows that are gone go first, so their unique values are free
+    let kept: HashSet<&usize> = ids.iter().collect();
+    for (id, (rowid, _)) in &old {
+        if !kept.contains(id) {
+            delete.execute([rowid])?;
+        }
+    }
+    let mut written = HashMap::new();
+    for (r, id) in rows.iter().zip(ids) {
+        let (rowid, values) = match old.get(id) {
+            Some((rowid, before)) => {
+                // a cell that shows what is on the database is left as it
+                // is, NULL or empty
+                let mut values = Vec::new();
+                let mut changed = Vec::new();
+                for (c, name) in columns.iter().enumerate() {
+                    let was = position(&read, name)
+                        .and_then(|p| before.get(p).cloned().flatten());
+                    let shown = r.get(c).map_or("", |v| v.as_str());
+                    match was.as_deref().unwrap_or("") == shown {
+                        true => values.push(was),
+                        false => {
+                            changed.push(c);
+                            values.push(value(c, r));
+                        }
+                    }
+                }
+                if !changed.is_empty() {
+                    let assignments: Vec<String> = changed
+                        .iter()
+                        .map(|c| format!("{} = ?", names[*c]))
+                        .collect();
+                    let mut params: Vec<Value> =
+                        changed.iter().map(|c| bind(&values[*c])).collect();
+                    params.push(Value::Integer(*rowid));
+                    tx.prepare_cached(&format!(
+                        "UPDATE {table} SET {} WHERE {by} = ?",
+                        assignments.join(", ")
+                    ))?
+                    .execute(params_from_iter(params))?;
+                }
+                (*rowid, values)
+            }
+            None => {
+                let values: Vec<Option<String>> =
+                    (0..columns.len()).map(|c| value(c, r)).collect();
+                insert.execute(params_from_iter(values.iter().map(bind)))?;
+                (tx.last_insert_rowid(), values)
+            }
+        };
+        written.insert(*id, (rowid, values));
+    }
+    drop((insert, delete));
+    tx.commit()
+        .wrap_err_with(|| format!("Could not write {}", path.display()))?;
+
+    source.rows = Some(written);
+    source.columns = columns;
+    source.rowid = Some(by);
+    if !source.tables.contains(&source.table) {
+        source.tables.push(source.table.clone());
+    }
+    Ok(())
+}
+
+/// Run a query over the table as `this`, like [`sql::query`], next to the
+/// other tables of the database it was read from. The table is also named
+/// as it is on the database, and both names see the changes not saved.
+pub fn query(
+    path: &Path,
+    table: &str,
+    header: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+    sql: &str,
+) -> Result<Vec<Vec<String>>> {
+    let conn = sql::open_table(header, types, rows)?;
+    if path.exists() {
+        conn.execute("ATTACH DATABASE ?1 AS db", [path.to_string_lossy()])
+            .wrap_err_with(|| format!("Could not open {}", path.display()))?;
+    }
+    // temp comes before the attached database when looking up a name
+    conn.execute(
+        &format!(
+            "CREATE TEMP VIEW {} AS SELECT * FROM main.{}",
+            quote(table),
+            quote(sql::TABLE)
+        ),
+        [],
+    )?;
+    sql::select(&conn, sql)
+}
+
+/// The SQL that creates the table and inserts the rows, to be run by
+/// `sqlite3`. Empty cells are NULL, and numbers are numbers when the column
+/// is numeric.
+pub fn dump(
+    table: &str,
+    columns: &[String],
+    types: &[ColumnType],
+    rows: &[Vec<String>],
+) -> String {
+    let columns = sql::column_names(columns);
+    let type_of = |c: usize| types.get(c).copied().unwrap_or_default();
+
+    let mut r = sql::create_statement(table, &columns, types) + ";\n";
+    for row in rows {
+        let values: Vec<String> = (0..columns.len())
+            .map(|c| {
+                let value = row.get(c).map_or("", |v| v.trim());
+                let numeric = match type_of(c) {
+                    ColumnType::Integer => value.parse::<i64>().is_ok(),
+                    ColumnType::Float => value.parse::<f64>().is_ok(),
+                    _ => false,
+                };
+                match value {
+                    "" => "NULL".to_string(),
+                    value if numeric => value.to_string(),
+                    _ => format!("'{}'", row[c].replace('\'', "''")),
+                }
+            })
+            .collect();
+        r.push_str(&format!(
+            "INSERT INTO {} VALUES ({});\n",
+            quote(table),
+            values.join(", ")
+        ));
+    }
+    r
+}
+
+/// Open a database that must exist, so a typo does not create an empty one.
+fn open(path: &Path) -> Result<Connection> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .wrap_err_with(|| format!("Could not open {}", path.display()))
+}
+
+/// The tables of a database, in the order they were created.
+fn tables(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master \
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY rowid",
+    )?;
+    let names = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(names)
+}
+
+/// The name the rowid of a table can be read by, the first of `rowid`, `oid`
+/// and `_rowid_` that is not the name of a column. `None` when every one is,
+/// or the table is WITHOUT ROWID.
+fn rowid(conn: &Connection, table: &str) -> Result<Option<String>> {
+    let columns = table_columns(conn, table)?;
+    let name = ["rowid", "oid", "_rowid_"].into_iter().find(|name| {
+        !columns.iter().any(|(c, _)| c.eq_ignore_ascii_case(name))
+    });
+    let readable = |name: &&str| {
+        conn.prepare(&format!("SELECT {name} FROM {} LIMIT 0", quote(table)))
+            .is_ok()
+    };
+    Ok(name.filter(readable).map(String::from))
+}
+
+/// The names of the columns of a table, and if they are NOT NULL.
+fn table_columns(
+    conn: &Connection,
+    table: &str,
+) -> Result<Vec<(String, bool)>> {
+    let mut stmt =
+        conn.prepare(&format!("PRAGMA table_info({})", quote(table)))?;
+    let columns = stmt
+        .query_map([], |row| Ok((row.get(1)?, row.get(3)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(columns)
+}
@@ -0,0 +1,172 @@
+use std::cmp::Ordering;
+
+use itertools::Itertools;
+
+use crate::types::ColumnType;
+
+/// Aggregates of a column, shown on the statistics popup.
+#[derive(Clone, Debug, Default)]
+pub struct ColumnStats {
+    /// Number of values, including empty ones.
+    pub count: usize,
+    pub distinct: usize,
+    /// Number of empty values.
+    pub nulls: usize,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    /// Only present for numeric columns.
+    pub numeric: Option<NumericStats>,
+    /// The most frequent values, with how many times they appear.
+    pub top: Vec<(String, usize)>,
+    /// Count of numeric values falling on each bin, from min to max. Empty
+    /// for non numeric columns.
+    pub histogram: Vec<u64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NumericStats {
+    pub sum: f64,
+    pub mean: f64,
+    pub median: f64,
+    /// Population standard deviation.
+    pub stddev: f64,
+}
+
+impl ColumnStats {
+    /// Compute the aggregates of the values of a column. `top_n` is how many
+    /// frequent values to keep and `bins` the size of the histogram.
+    pub fn new(
+        values: &[&str],
+        column_type: ColumnType,
+        top_n: usize,
+        bins: usize,
+    ) -> Self {
+        let present: Vec<&str> =
+            values.iter().copied().filter(|v| !v.trim().is_empty()).collect();
+
+        let top: Vec<(String, usize)> = present
+            .iter()
+            .counts()
+            .into_iter()
+            .sorted_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)))
+            .map(|(v, n)| (v.to_string(), n))
+            .collect();
+
+        let mut stats = ColumnStats {
+            count: values.len(),
+            distinct: top.len(),
+            nulls: values.len() - present.len(),
+            top: top.into_iter().take(top_n).collect(),
+            ..Default::default()
+        };
+
+        if column_type.is_numeric() {
+            let mut numbers: Vec<f64> =
+                present.iter().filter_map(|v| v.trim().parse().ok()).collect();
+            numbers.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+            stats.min = numbers.first().map(|n| n.to_string());
+            stats.max = numbers.last().map(|n| n.to_string());
+            stats.numeric = numeric_stats(&numbers);
+            stats.histogram = histogram(&numbers, bins);
+        } else {
+            stats.min = present.iter().min().map(|v| v.to_string());
+            stats.max = present.iter().max().map(|v| v.to_string());
+        }
+
+        stats
+    }
+}
+
+/// `numbers` must be sorted.
+fn numeric_stats(numbers: &[f64]) -> Option<NumericStats> {
+    if numbers.is_empty() {
+        return None;
+    }
+
+    let n = numbers.len() as f64;
+    let sum: f64 = numbers.iter().sum();
+    let mean = sum / n;
+    let median = if numbers.len().is_multiple_of(2) {
+        let mid = numbers.len() / 2;
+        (numbers[mid - 1] + numbers[mid]) / 2.0
+    } else {
+        numbers[numbers.len() / 2]
+    };
+    let variance = numbers.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+
+    Some(NumericStats { sum, mean, median, stddev: variance.sqrt() })
+}
+
+/// `numbers` must be sorted.
+fn histogram(numbers: &[f64], bins: usize) -> Vec<u64> {
+    let (Some(min), Some(max)) = (numbers.first(), numbers.last()) else {
+        return Vec::new();
+    };
+    let bins = bins.max(1);
+    let mut histogram = vec![0; bins];
+    let width = (max - min) / bins as f64;
+
+    for n in numbers {
+        let bin = if width > 0.0 { ((n - min) / width) as usize } else { 0 };
+        histogram[bin.min(bins - 1)] += 1;
+    }
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_column() {
+        let values = ["3", "1", "", "2", "4", "2"];
+        let stats = ColumnStats::new(&values, ColumnType::Integer, 2, 3);
+
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.nulls, 1);
+        assert_eq!(stats.distinct, 4);
+        assert_eq!(stats.min.as_deref(), Some("1"));
+        assert_eq!(stats.max.as_deref(), Some("4"));
+        assert_eq!(stats.top, [("2".to_string(), 2), ("1".to_string(), 1)]);
+        assert_eq!(stats.histogram, [1, 2, 2]);
+
+        let numeric = stats.numeric.unwrap();
+        assert_eq!(numeric.sum, 12.0);
+        assert_eq!(numeric.mean, 2.4);
+        assert_eq!(numeric.median, 2.0);
+        assert!((numeric.stddev - 1.019_803_9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn numbers_compare_as_numbers() {
+        let values = ["10", "9", "-1.5"];
+        let stats = ColumnStats::new(&values, ColumnType::Float, 5, 1);
+
+        assert_eq!(stats.min.as_deref(), Some("-1.5"));
+        assert_eq!(stats.max.as_deref(), Some("10"));
+        assert_eq!(stats.numeric.unwrap().median, 9.0);
+        assert_eq!(stats.histogram, [3]);
+    }
+
+    #[test]
+    fn text_column() {
+        let values = ["b", "a", " ", "b"];
+        let stats = ColumnStats::new(&values, ColumnType::Text, 5, 4);
+
+        assert_eq!(stats.nulls, 1);
+        assert_eq!(stats.min.as_deref(), Some("a"));
+        assert_eq!(stats.max.as_deref(), Some("b"));
+        assert!(stats.numeric.is_none());
+        assert!(stats.histogram.is_empty());
+    }
+
+    #[test]
+    fn even_median_and_single_value_histogram() {
+        let numbers = [1.0, 2.0, 3.0, 10.0];
+        assert_eq!(numeric_stats(&numbers).unwrap().median, 2.5);
+        assert!(numeric_stats(&[]).is_none());
+        assert_eq!(histogram(&[5.0, 5.0], 3), [2, 0, 0]);
+        assert!(histogram(&[], 3).is_empty());
+    }
+}
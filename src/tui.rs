@@ -84,7 +84,6 @@ impl Tui {
         self
     }
 
-    #[allow(dead_code)]
     pub fn mouse(mut self, mouse: bool) -> Self {
         self.mouse = mouse;
         self
@@ -225,7 +224,6 @@ impl Tui {
         self.cancellation_token.cancel();
     }
 
-    #[allow(dead_code)]
     pub fn suspend(&mut self) -> Result<()> {
         self.exit()?;
         #[cfg(not(windows))]
@@ -233,7 +231,6 @@ impl Tui {
         Ok(())
     }
 
-    #[allow(dead_code)]
     pub fn resume(&mut self) -> Result<()> {
         self.run()?;
         Ok(())
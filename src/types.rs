@@ -0,0 +1,253 @@
+use serde::Deserialize;
+
+/// The type of a column, inferred from the values in it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// The column has no values, so anything goes.
+    #[default]
+    Empty,
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    DateTime,
+    Email,
+    Text,
+}
+
+impl ColumnType {
+    /// The most specific type of a single value.
+    pub fn of(value: &str) -> Self {
+        let value = value.trim();
+        if value.is_empty() {
+            ColumnType::Empty
+        } else if is_integer(value) {
+            ColumnType::Integer
+        } else if is_float(value) {
+            ColumnType::Float
+        } else if is_boolean(value) {
+            ColumnType::Boolean
+        } else if is_date(value) {
+            ColumnType::Date
+        } else if is_datetime(value) {
+            ColumnType::DateTime
+        } else if is_email(value) {
+            ColumnType::Email
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    /// The narrowest type that can hold values of both types.
+    pub fn unify(self, other: Self) -> Self {
+        use ColumnType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Empty, t) | (t, Empty) => t,
+            (Integer, Float) | (Float, Integer) => Float,
+            (Date, DateTime) | (DateTime, Date) => DateTime,
+            _ => Text,
+        }
+    }
+
+    /// If `value` can be stored on a column of this type. Empty values are
+    /// always accepted, they mean a missing value, and an empty column
+    /// accepts anything.
+    pub fn accepts(&self, value: &str) -> bool {
+        let t = ColumnType::of(value);
+        t == ColumnType::Empty
+            || *self == ColumnType::Empty
+            || self.unify(t) == *self
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, ColumnType::Integer | ColumnType::Float)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Date => "date",
+            ColumnType::DateTime => "datetime",
+            ColumnType::Email => "email",
+            ColumnType::Text => "text",
+        }
+    }
+}
+
+/// Infer the type of a column from all of its values.
+pub fn infer<'a>(values: impl IntoIterator<Item = &'a str>) -> ColumnType {
+    values
+        .into_iter()
+        .map(ColumnType::of)
+        .fold(ColumnType::Empty, ColumnType::unify)
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+    !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && !zero_padded(value)
+}
+
+fn is_float(value: &str) -> bool {
+    // `parse` also accepts "inf" and "NaN", which are more likely to be text
+    value.chars().any(|c| c.is_ascii_digit())
+        && !zero_padded(value)
+        && value.chars().all(|c| "0123456789+-.eE".contains(c))
+        && value.parse::<f64>().is_ok()
+}
+
+/// A number with zeros before its first digit, like the `007` of a zip code
+/// or an id, which would be lost when written as a number.
+fn zero_padded(value: &str) -> bool {
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let mut chars = digits.chars();
+    chars.next() == Some('0')
+        && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+fn is_boolean(value: &str) -> bool {
+    ["true", "false", "yes", "no"].iter().any(|b| value.eq_ignore_ascii_case(b))
+}
+
+fn parse_number(value: &str, digits: usize) -> Option<u32> {
+    if value.len() != digits || !value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// `YYYY-MM-DD`
+fn is_date(value: &str) -> bool {
+    let mut parts = value.split('-');
+    let (Some(y), Some(m), Some(d), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    let (Some(y), Some(m), Some(d)) =
+        (parse_number(y, 4), parse_number(m, 2), parse_number(d, 2))
+    else {
+        return false;
+    };
+
+    let leap = (y % 4 == 0 && y % 100 != 0) || y % 400 == 0;
+    let days = match m {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&d)
+}
+
+/// `HH:MM`, `HH:MM:SS` or `HH:MM:SS.fff`
+fn is_time(value: &str) -> bool {
+    let (hms, fraction) = value.split_once('.').unwrap_or((value, "0"));
+    if fraction.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    let parts: Vec<_> = hms.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return false;
+    }
+    parts
+        .iter()
+        .zip([23, 59, 60])
+        .all(|(part, max)| parse_number(part, 2).is_some_and(|n| n <= max))
+}
+
+/// A date and a time separated by `T` or a space, optionally followed by a
+/// `Z` or a `+HH:MM` offset.
+fn is_datetime(value: &str) -> bool {
+    let Some((date, time)) = value.split_once(['T', ' ']) else {
+        return false;
+    };
+    let time = time.strip_suffix('Z').unwrap_or(time);
+    let time = match time.rfind(['+', '-']) {
+        Some(i) if is_time(&time[i + 1..]) => &time[..i],
+        _ => time,
+    };
+    is_date(date) && is_time(time)
+}
+
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !value.contains(char::is_whitespace)
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+}
+
+/// Guess if the first row of the matrix is a header, the same way most
+/// spreadsheet tools do: it is a header if some column has a specific type
+/// in the rest of the rows, and the first value does not fit in it.
+pub fn has_header(matrix: &[Vec<String>]) -> bool {
+    let Some((first, body)) = matrix.split_first() else {
+        return false;
+    };
+    if body.is_empty() || first.iter().any(|v| v.trim().is_empty()) {
+        return false;
+    }
+
+    first.iter().enumerate().any(|(col, name)| {
+        let t =
+            infer(body.iter().filter_map(|r| r.get(col)).map(|v| v.as_str()));
+        !matches!(t, ColumnType::Text | ColumnType::Empty) && !t.accepts(name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infer_numbers() {
+        assert_eq!(infer(["1", "-2", "+30"]), ColumnType::Integer);
+        assert_eq!(infer(["1", "2.5", "1e3"]), ColumnType::Float);
+        assert_eq!(infer(["0", "0.5", "-0.25"]), ColumnType::Float);
+        assert_eq!(infer(["inf", "NaN"]), ColumnType::Text);
+    }
+
+    #[test]
+    fn infer_zero_padded_as_text() {
+        assert_eq!(infer(["007"]), ColumnType::Text);
+        assert_eq!(infer(["02134", "10001"]), ColumnType::Text);
+        assert_eq!(infer(["-01"]), ColumnType::Text);
+        assert_eq!(infer(["00.5"]), ColumnType::Text);
+        assert_eq!(infer(["0", "10"]), ColumnType::Integer);
+    }
+
+    #[test]
+    fn infer_other_types() {
+        assert_eq!(infer(["", " "]), ColumnType::Empty);
+        assert_eq!(infer(["true", "No", ""]), ColumnType::Boolean);
+        assert_eq!(infer(["2024-02-29"]), ColumnType::Date);
+        assert_eq!(infer(["2023-02-29"]), ColumnType::Text);
+        assert_eq!(
+            infer(["2024-01-01", "2024-01-01T10:00:00Z"]),
+            ColumnType::DateTime
+        );
+        assert_eq!(infer(["a@b.com"]), ColumnType::Email);
+        assert_eq!(infer(["1", "a"]), ColumnType::Text);
+    }
+
+    #[test]
+    fn header_is_detected() {
+        let matrix = |rows: &[[&str; 2]]| -> Vec<Vec<String>> {
+            rows.iter().map(|r| r.map(String::from).to_vec()).collect()
+        };
+        assert!(has_header(&matrix(&[["id", "zip"], ["1", "007"]])));
+        assert!(!has_header(&matrix(&[["a", "b"], ["c", "d"]])));
+    }
+}
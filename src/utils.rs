@@ -1,5 +1,10 @@
+use std::path::Path;
+
+use color_eyre::eyre::{Result, WrapErr};
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 
+use crate::codec;
+
 pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::new(
         Direction::Vertical,
@@ -21,3 +26,90 @@ pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     )
     .split(popup_layout[1])[1]
 }
+
+/// Spreadsheet style name of a column: A, B, ..., Z, AA, AB...
+pub fn column_name(mut col: usize) -> String {
+    let mut name = String::new();
+    loop {
+        name.insert(0, (b'A' + (col % 26) as u8) as char);
+        if col < 26 {
+            break;
+        }
+        col = col / 26 - 1;
+    }
+    name
+}
+
+/// Index of a column from its spreadsheet style name, the inverse of
+/// [`column_name`]. Case insensitive.
+pub fn column_index(name: &str) -> Option<usize> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    name.to_ascii_uppercase()
+        .bytes()
+        .try_fold(0usize, |acc, b| {
+            acc.checked_mul(26)?.checked_add((b - b'A') as usize + 1)
+        })
+        .map(|i| i - 1)
+}
+
+/// Write a file next to `path` first and then rename it, so the file is
+/// never left half written. It is compressed when the file is. A symlink is
+/// followed, so the link stays and the file it points to is written, and the
+/// file keeps its permissions.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let target = std::fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    let contents = codec::encode(&target, contents.as_ref())?;
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = target.with_file_name(format!(".{name}.tmp"));
+    let permissions = std::fs::metadata(&target).map(|m| m.permissions());
+    let written = std::fs::write(&tmp, contents)
+        .and_then(|_| match permissions {
+            Ok(permissions) => std::fs::set_permissions(&tmp, permissions),
+            Err(_) => Ok(()),
+        })
+        .and_then(|_| std::fs::rename(&tmp, &target));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    written.wrap_err_with(|| format!("Could not write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn column_names_round_trip() {
+        for (i, name) in [(0, "A"), (25, "Z"), (26, "AA"), (701, "ZZ")] {
+            assert_eq!(column_name(i), name);
+            assert_eq!(column_index(name), Some(i));
+        }
+        assert_eq!(column_index("a1"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_links_and_permissions() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir()
+            .join(format!("write-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("data.csv");
+        let link = dir.join("link.csv");
+        std::fs::write(&file, "a\n").unwrap();
+        std::fs::set_permissions(&file, PermissionsExt::from_mode(0o600))
+            .unwrap();
+        let _ = std::fs::remove_file(&link);
+        symlink(&file, &link).unwrap();
+
+        write_atomic(&link, "b\n").unwrap();
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "b\n");
+        let mode = file.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use calamine::{open_workbook_auto, Data, Reader};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+
+use crate::types::ColumnType;
+
+/// The sheet of a workbook a table was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sheet {
+    pub name: String,
+    /// The names of every sheet of the workbook, in their order.
+    pub workbook: Vec<String>,
+}
+
+impl Sheet {
+    /// The sheet of a workbook that has no other.
+    pub fn single(name: &str) -> Self {
+        Self { name: name.to_string(), workbook: vec![name.to_string()] }
+    }
+
+    /// If the workbook has other sheets, which would be lost by writing the
+    /// table over it.
+    pub fn has_others(&self) -> bool {
+        self.workbook.iter().any(|s| *s != self.name)
+    }
+}
+
+impl Default for Sheet {
+    fn default() -> Self {
+        Self::single("Sheet1")
+    }
+}
+
+/// Read a sheet of a workbook, or its first sheet. Numbers and booleans are
+/// read as they are written in CSV, and dates as `YYYY-MM-DD`, with the time
+/// when it is not midnight.
+pub fn load(
+    path: &Path,
+    sheet: Option<&str>,
+) -> Result<(Vec<Vec<String>>, Sheet)> {
+    let mut workbook = open_workbook_auto(path)
+        .wrap_err_with(|| format!("Could not read {}", path.display()))?;
+    let names = workbook.sheet_names();
+    let name = match sheet {
+        Some(name) if names.iter().any(|n| n == name) => name.to_string(),
+        Some(name) => {
+            return Err(eyre!("No sheet {name} on {}", path.display()))
+        }
+        None => names
+            .first()
+            .cloned()
+            .ok_or(eyre!("{} has no sheets", path.display()))?,
+    };
+
+    let range = workbook.worksheet_range(&name).wrap_err_with(|| {
+        format!("Could not read {name} of {}", path.display())
+    })?;
+    let matrix =
+        range.rows().map(|row| row.iter().map(cell).collect()).collect();
+    Ok((matrix, Sheet { name, workbook: names }))
+}
+
+/// Write the table on a workbook with a single sheet. Cells of integer,
+/// float, boolean, date and datetime columns are written as numbers,
+/// booleans and dates, so they keep their type on the spreadsheet.
+pub fn to_bytes(
+    sheet: &str,
+    matrix: &[Vec<String>],
+    types: &[ColumnType],
+    has_header: bool,
+) -> Result<Vec<u8>> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet).wrap_err("Invalid sheet name")?;
+
+    let bold = Format::new().set_bold();
+    let date = Format::new().set_num_format("yyyy-mm-dd");
+    let datetime = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+    for (r, row) in matrix.iter().enumerate() {
+        for (c, value) in row.iter().enumerate() {
+            if value.is_empty() {
+                continue;
+            }
+            let (Ok(r), Ok(c)) = (u32::try_from(r), u16::try_from(c)) else {
+                return Err(eyre!("The table is too big for a sheet"));
+            };
+            let trimmed = value.trim();
+            let t = types.get(c as usize).copied().unwrap_or_default();
+            let written = match t {
+                _ if has_header && r == 0 => {
+                    worksheet.write_string_with_format(r, c, value, &bold)
+                }
+                ColumnType::Integer | ColumnType::Float
+                    if let Ok(n) = trimmed.parse::<f64>() =>
+                {
+                    worksheet.write_number(r, c, n)
+                }
+                ColumnType::Boolean if let Some(b) = boolean(trimmed) => {
+                    worksheet.write_boolean(r, c, b)
+                }
+                ColumnType::Date | ColumnType::DateTime
+                    if let Ok(d) = ExcelDateTime::parse_from_str(trimmed) =>
+                {
+                    let format = match t {
+                        ColumnType::Date => &date,
+                        _ => &datetime,
+                    };
+                    worksheet.write_datetime_with_format(r, c, &d, format)
+                }
+                _ => worksheet.write_string(r, c, value),
+            };
+            written.wrap_err("Could not write the sheet")?;
+        }
+    }
+    worksheet.autofit();
+
+    workbook.save_to_buffer().wrap_err("Could not write the workbook")
+}
+
+fn cell(data: &Data) -> String {
+    match data {
+        Data::DateTime(d) if let Some(d) = d.as_datetime() => {
+            let text = d.to_string();
+            match text.strip_suffix(" 00:00:00") {
+                Some(date) => date.to_string(),
+                None => text,
+            }
+        }
+        data => data.to_string(),
+    }
+}
+
+fn boolean(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" => Some(true),
+        "false" | "no" => Some(false),
+        _ => None,
+    }
+}